@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use editor::{update_and_render, State};
+use platform_types::{CharDim, Input, Sizes};
+
+/// About 10 MB, so the time taken to copy the whole buffer would swamp everything else.
+fn large_buffer() -> State {
+    let mut state: State = include_str!("../../../text/slipsum.txt").repeat(800).into();
+
+    let char_dim = CharDim { w: 8.0, h: 16.0 };
+    update_and_render(
+        &mut state,
+        Input::SetSizes(Sizes! {
+            screen_w: 1024.0,
+            screen_h: 768.0,
+            text_char_dim: char_dim,
+            status_char_dim: char_dim,
+        }),
+    );
+
+    state
+}
+
+fn render_large_file_benchmark(c: &mut Criterion) {
+    let mut state = large_buffer();
+    // Somewhere in the middle, so neither end of the buffer is on screen.
+    update_and_render(&mut state, Input::ScrollVertically(16.0 * 50_000.0));
+
+    c.bench_function("scrolling and rendering a large file", move |b| {
+        b.iter(|| {
+            update_and_render(&mut state, black_box(Input::ScrollVertically(-16.0)));
+            update_and_render(&mut state, black_box(Input::ScrollVertically(16.0)))
+        })
+    });
+}
+
+criterion_group!(render_large_file, render_large_file_benchmark);
+criterion_main!(render_large_file);
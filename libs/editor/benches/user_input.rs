@@ -7,6 +7,12 @@ fn slipsum_buffer() -> State {
     include_str!("../../../text/slipsum.txt").into()
 }
 
+fn million_line_buffer() -> State {
+    "Just a short line, so the line count dominates.\n"
+        .repeat(1_000_000)
+        .into()
+}
+
 fn cursor_movement_and_selection_benchmark(c: &mut Criterion) {
     c.bench_function("repeated movement and selection", |b| {
         b.iter_batched(
@@ -83,8 +89,29 @@ fn cursor_movement_and_selection_benchmark(c: &mut Criterion) {
     });
 }
 
+fn million_line_movement_benchmark(c: &mut Criterion) {
+    let mut state = million_line_buffer();
+
+    c.bench_function("moving around a million lines", move |b| {
+        b.iter(|| {
+            update_and_render(
+                &mut state,
+                black_box(Input::MoveAllCursors(Move::ToBufferEnd)),
+            );
+            update_and_render(&mut state, black_box(Input::MoveAllCursors(Move::Up)));
+            update_and_render(&mut state, black_box(Input::MoveAllCursors(Move::Up)));
+            update_and_render(&mut state, black_box(Input::MoveAllCursors(Move::Down)));
+            update_and_render(
+                &mut state,
+                black_box(Input::MoveAllCursors(Move::ToBufferStart)),
+            )
+        })
+    });
+}
+
 criterion_group!(
     cursor_movement_and_selection,
-    cursor_movement_and_selection_benchmark
+    cursor_movement_and_selection_benchmark,
+    million_line_movement_benchmark
 );
 criterion_main!(cursor_movement_and_selection);
@@ -41,7 +41,7 @@ impl From<ByteIndex> for ByteLength {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Cursor {
     pub position: Position,
     pub sticky_offset: CharOffset,
@@ -85,8 +85,24 @@ impl Borrow<Position> for &Cursor {
 pub trait MultiCursorBuffer: Borrow<Vec1<Cursor>> + BorrowMut<Vec1<Cursor>> {
     fn insert(&mut self, ch: char);
 
+    /// If `s` has exactly as many lines as there are cursors, then each cursor gets one line.
+    /// Otherwise every cursor gets all of `s`.
+    fn insert_string(&mut self, s: String);
+
+    /// The selected text of each cursor, in cursor order. Cursors without a selection give an
+    /// empty string.
+    fn copy_selections(&self) -> Vec<String>;
+
+    /// Like `copy_selections`, but also removes the selected text.
+    fn cut_selections(&mut self) -> Vec<String>;
+
+    /// Removes each cursor's selection, or the grapheme before it if it has no selection.
     fn delete(&mut self);
 
+    /// Removes each cursor's selection, or if it has none, the text between it and where `r#move`
+    /// would take it.
+    fn delete_to(&mut self, r#move: Move);
+
     fn move_all_cursors(&mut self, r#move: Move) {
         for i in 0..self.cursors().len() {
             self.move_cursor(i, r#move)
@@ -103,8 +119,12 @@ pub trait MultiCursorBuffer: Borrow<Vec1<Cursor>> + BorrowMut<Vec1<Cursor>> {
 
     fn extend_selection(&mut self, index: usize, r#move: Move);
 
+    /// Whether `position` is in the buffer. An offset past the end of its line is out of bounds,
+    /// even if counting that many chars from the start of the line would reach a later line.
     fn in_bounds<P: Borrow<Position>>(&self, position: P) -> bool {
-        self.find_index(position) != None
+        let position = position.borrow();
+        self.nearest_valid_position_on_same_line(position) == Some(*position)
+            && self.find_index(position) != None
     }
 
     fn find_index<P: Borrow<Position>>(&self, position: P) -> Option<ByteIndex>;
@@ -119,3 +139,6 @@ pub trait MultiCursorBuffer: Borrow<Vec1<Cursor>> + BorrowMut<Vec1<Cursor>> {
         self.borrow_mut()
     }
 }
+
+#[cfg(test)]
+mod tests;
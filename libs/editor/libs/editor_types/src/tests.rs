@@ -0,0 +1,88 @@
+use super::*;
+use macros::{borrow, borrow_mut};
+use platform_types::pos;
+
+/// Just enough of a buffer to try out the default methods of `MultiCursorBuffer`. Positions are
+/// turned into indexes by counting chars from the start of the line, the way a rope does, so
+/// offsets past the end of a line run on into the next one.
+struct LinesBuffer {
+    lines: Vec<&'static str>,
+    cursors: Vec1<Cursor>,
+}
+
+borrow!(<Vec1<Cursor>> for LinesBuffer : s in &s.cursors);
+borrow_mut!(<Vec1<Cursor>> for LinesBuffer : s in &mut s.cursors);
+
+impl LinesBuffer {
+    fn new(lines: Vec<&'static str>) -> Self {
+        LinesBuffer {
+            lines,
+            cursors: d!(),
+        }
+    }
+}
+
+impl MultiCursorBuffer for LinesBuffer {
+    fn insert(&mut self, _: char) {}
+    fn insert_string(&mut self, _: String) {}
+    fn copy_selections(&self) -> Vec<String> {
+        Vec::new()
+    }
+    fn cut_selections(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+    fn delete(&mut self) {}
+    fn delete_to(&mut self, _: Move) {}
+    fn move_cursor(&mut self, _: usize, _: Move) {}
+    fn extend_selection(&mut self, _: usize, _: Move) {}
+
+    fn find_index<P: Borrow<Position>>(&self, position: P) -> Option<ByteIndex> {
+        let position = position.borrow();
+        if position.line >= self.lines.len() {
+            return None;
+        }
+        let line_start: usize = self.lines[..position.line].iter().map(|l| l.len()).sum();
+        let index = line_start + position.offset.0;
+        let total: usize = self.lines.iter().map(|l| l.len()).sum();
+        if index <= total {
+            Some(ByteIndex(index))
+        } else {
+            None
+        }
+    }
+
+    fn nearest_valid_position_on_same_line<P: Borrow<Position>>(&self, p: P) -> Option<Position> {
+        let p = p.borrow();
+        let line = self.lines.get(p.line)?;
+        Some(Position {
+            offset: std::cmp::min(p.offset, CharOffset(line.len())),
+            ..*p
+        })
+    }
+}
+
+#[test]
+fn in_bounds_accepts_positions_up_to_the_end_of_each_line() {
+    let buffer = LinesBuffer::new(vec!["ab\n", "cdef"]);
+
+    assert!(buffer.in_bounds(pos! {l 0 o 0}));
+    assert!(buffer.in_bounds(pos! {l 0 o 3}));
+    assert!(buffer.in_bounds(pos! {l 1 o 4}));
+}
+
+#[test]
+fn in_bounds_rejects_offsets_that_run_past_the_end_of_the_line() {
+    let buffer = LinesBuffer::new(vec!["ab\n", "cdef"]);
+
+    // Counting 5 chars from the start of the first line lands on the second line.
+    assert!(buffer.find_index(pos! {l 0 o 5}).is_some());
+    assert!(!buffer.in_bounds(pos! {l 0 o 5}));
+}
+
+#[test]
+fn in_bounds_rejects_positions_past_the_last_line() {
+    let buffer = LinesBuffer::new(vec!["ab\n", "cdef"]);
+
+    assert!(!buffer.in_bounds(pos! {l 1 o 5}));
+    assert!(!buffer.in_bounds(pos! {l 2 o 0}));
+}
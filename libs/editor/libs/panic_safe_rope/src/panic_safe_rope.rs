@@ -1,12 +1,55 @@
 ///! A wrapper around `ropey::Rope` that checks the panic conditions at runtime and
 ///! changes the return type of some methods with the aim of preventing panics.
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Rope {
     rope: ropey::Rope,
+    line_breaks: LineBreaks,
+    /// When `line_breaks` is not `LineBreaks::Unicode`, a copy of the text with every char that
+    /// does not break lines replaced with a space. `ropey` splits that copy into the lines we
+    /// want, so its line index answers the line related methods, in logarithmic time, even
+    /// though `ropey` itself always uses `LineBreaks::Unicode`.
+    line_index: Option<ropey::Rope>,
 }
 
-use macros::{fmt_debug, fmt_display};
+/// Which chars split the text into lines, for all of the line related methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineBreaks {
+    /// Only LF, like `grep -n` and most compilers.
+    LFOnly,
+    /// CRLF, along with lone CRs and LFs.
+    CROrLF,
+    /// Everything Unicode considers a mandatory line break. In addition to `CROrLF`'s ones,
+    /// that is VT, FF, NEL, LS and PS. This is what `ropey` does.
+    Unicode,
+}
+
+d!(for LineBreaks: LineBreaks::Unicode);
+
+impl LineBreaks {
+    /// The inverse of the `Display` impl, ignoring case and spaces.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name.split_whitespace().collect();
+        match name.to_ascii_uppercase().as_str() {
+            "LF" => Some(LineBreaks::LFOnly),
+            "CRORLF" => Some(LineBreaks::CROrLF),
+            "UNICODE" => Some(LineBreaks::Unicode),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LineBreaks {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            LineBreaks::LFOnly => "LF",
+            LineBreaks::CROrLF => "CR or LF",
+            LineBreaks::Unicode => "Unicode",
+        })
+    }
+}
+
+use macros::{d, fmt_debug, fmt_display};
 use std::io;
 use std::iter::FromIterator;
 use std::ops::{Bound, RangeBounds};
@@ -24,20 +67,44 @@ macro_rules! some_if {
 impl Rope {
     #[inline]
     pub fn new() -> Self {
-        Rope {
-            rope: ropey::Rope::new(),
-        }
+        ropey::Rope::new().into()
     }
 
     #[inline]
     pub fn from_str(text: &str) -> Self {
-        Rope {
-            rope: ropey::Rope::from_str(text),
-        }
+        ropey::Rope::from_str(text).into()
     }
 
     pub fn from_reader<T: io::Read>(reader: T) -> io::Result<Self> {
-        ropey::Rope::from_reader(reader).map(|rope| Rope { rope })
+        ropey::Rope::from_reader(reader).map(Rope::from)
+    }
+
+    #[inline]
+    pub fn line_breaks(&self) -> LineBreaks {
+        self.line_breaks
+    }
+
+    /// Changes which chars split the text into lines. This takes time proportional to the length
+    /// of the text, unless `line_breaks` is `LineBreaks::Unicode`.
+    pub fn set_line_breaks(&mut self, line_breaks: LineBreaks) {
+        self.line_breaks = line_breaks;
+        self.line_index = match line_breaks {
+            LineBreaks::Unicode => None,
+            _ => Some(
+                self.rope
+                    .chars()
+                    .map(|c| line_index_char(line_breaks, c))
+                    .collect::<String>()
+                    .into(),
+            ),
+        };
+    }
+
+    /// The rope that `ropey` splits into lines the way `line_breaks` says to. Its char indexes
+    /// are the same as `rope`'s.
+    #[inline]
+    fn lines_rope(&self) -> &ropey::Rope {
+        self.line_index.as_ref().unwrap_or(&self.rope)
     }
 
     pub fn write_to<T: io::Write>(&self, writer: T) -> io::Result<()> {
@@ -56,7 +123,7 @@ impl Rope {
 
     #[inline]
     pub fn len_lines(&self) -> usize {
-        self.rope.len_lines()
+        self.lines_rope().len_lines()
     }
 
     pub fn capacity(&self) -> usize {
@@ -70,15 +137,26 @@ impl Rope {
     /// Returns `None` and does not mutate if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
     #[inline]
     pub fn insert(&mut self, char_idx: usize, text: &str) -> Option<()> {
-        some_if!(char_idx <= self.len_chars() => self.rope.insert(char_idx, text))
+        some_if!(char_idx <= self.len_chars() => {
+            self.rope.insert(char_idx, text);
+            let line_breaks = self.line_breaks;
+            if let Some(ref mut index) = self.line_index {
+                let text: String = text.chars().map(|c| line_index_char(line_breaks, c)).collect();
+                index.insert(char_idx, &text);
+            }
+        })
     }
 
     /// Returns `None` and does not mutate if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
     #[inline]
     pub fn insert_char(&mut self, char_idx: usize, ch: char) -> Option<()> {
-        some_if!(
-            char_idx <= self.len_chars() => self.rope.insert_char(char_idx, ch)
-        )
+        some_if!(char_idx <= self.len_chars() => {
+            self.rope.insert_char(char_idx, ch);
+            let line_breaks = self.line_breaks;
+            if let Some(ref mut index) = self.line_index {
+                index.insert_char(char_idx, line_index_char(line_breaks, ch));
+            }
+        })
     }
 
     /// Returns `None` and does not mutate if the start of the range is greater than the end, or if the
@@ -90,21 +168,33 @@ impl Rope {
         let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
         let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
 
-        some_if!(
-            start <= end || end <= self.len_chars() => self.rope.remove(char_range)
-        )
+        some_if!(start <= end && end <= self.len_chars() => {
+            self.rope.remove(char_range);
+            if let Some(ref mut index) = self.line_index {
+                index.remove(start..end);
+            }
+        })
     }
 
     /// Returns `None` and does not mutate if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
     pub fn split_off(&mut self, char_idx: usize) -> Option<Self> {
-        some_if!(
-            char_idx <= self.len_chars() => self.rope.split_off(char_idx).into()
-        )
+        some_if!(char_idx <= self.len_chars() => {
+            let mut other: Self = self.rope.split_off(char_idx).into();
+            other.line_breaks = self.line_breaks;
+            other.line_index = self.line_index.as_mut().map(|index| index.split_off(char_idx));
+            other
+        })
     }
 
     /// Appends a `Rope` to the end of this one, consuming the other `Rope`.
     pub fn append(&mut self, other: Self) {
-        self.rope.append(other.rope)
+        self.rope.append(other.rope);
+        match (&mut self.line_index, other.line_index) {
+            (Some(index), Some(other_index)) if other.line_breaks == self.line_breaks => {
+                index.append(other_index)
+            }
+            _ => self.set_line_breaks(self.line_breaks),
+        }
     }
 
     /// Returns `None`  if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
@@ -116,7 +206,12 @@ impl Rope {
     /// Returns `None`  if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
     #[inline]
     pub fn byte_to_line(&self, byte_idx: usize) -> Option<usize> {
-        some_if!(byte_idx <= self.len_bytes() => self.rope.byte_to_line(byte_idx))
+        match self.line_breaks {
+            LineBreaks::Unicode => {
+                some_if!(byte_idx <= self.len_bytes() => self.rope.byte_to_line(byte_idx))
+            }
+            _ => self.char_to_line(self.byte_to_char(byte_idx)?),
+        }
     }
 
     /// Returns `None`  if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
@@ -128,25 +223,24 @@ impl Rope {
     /// Returns `None`  if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
     #[inline]
     pub fn char_to_line(&self, char_idx: usize) -> Option<usize> {
-        some_if!(
-            char_idx <= self.len_chars() => self.rope.char_to_line(char_idx)
-        )
+        some_if!(char_idx <= self.len_chars() => self.lines_rope().char_to_line(char_idx))
     }
 
     /// Returns `None`  if `line_idx` is out of bounds (i.e. `line_idx > len_lines()`).
     #[inline]
     pub fn line_to_byte(&self, line_idx: usize) -> Option<usize> {
-        some_if!(
-            line_idx <= self.len_lines() => self.rope.line_to_byte(line_idx)
-        )
+        match self.line_breaks {
+            LineBreaks::Unicode => some_if!(
+                line_idx <= self.len_lines() => self.rope.line_to_byte(line_idx)
+            ),
+            _ => self.char_to_byte(self.line_to_char(line_idx)?),
+        }
     }
 
     /// Returns `None`  if `line_idx` is out of bounds (i.e. `line_idx > len_lines()`).
     #[inline]
     pub fn line_to_char(&self, line_idx: usize) -> Option<usize> {
-        some_if!(
-            line_idx <= self.len_lines() => self.rope.line_to_char(line_idx)
-        )
+        some_if!(line_idx <= self.len_lines() => self.lines_rope().line_to_char(line_idx))
     }
 
     /// Returns `None`  if `byte_idx` is out of bounds (i.e. `byte_idx >= len_bytes()`).
@@ -165,12 +259,29 @@ impl Rope {
         )
     }
 
+    /// The number of chars in the given line, including its line break, if it has one. This is
+    /// the same as `line(line_idx)?.len_chars()`, but without walking the line's chunks.
+    ///
+    /// Returns `None`  if `line_idx` is out of bounds (i.e. `line_idx >= len_lines()`).
+    #[inline]
+    pub fn line_len_chars(&self, line_idx: usize) -> Option<usize> {
+        some_if!(line_idx < self.len_lines() => {
+            self.line_to_char(line_idx + 1)? - self.line_to_char(line_idx)?
+        })
+    }
+
     /// Returns `None`  if `line_idx` is out of bounds (i.e. `line_idx >= len_lines()`).
     #[inline]
     pub fn line(&self, line_idx: usize) -> Option<ropey::RopeSlice> {
-        some_if!(
-            line_idx < self.len_lines() => self.rope.line(line_idx)
-        )
+        match self.line_breaks {
+            LineBreaks::Unicode => some_if!(
+                line_idx < self.len_lines() => self.rope.line(line_idx)
+            ),
+            _ if line_idx < self.len_lines() => {
+                self.slice(self.line_to_char(line_idx)?..self.line_to_char(line_idx + 1)?)
+            }
+            _ => None,
+        }
     }
 
     /// Returns `None` if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
@@ -195,9 +306,12 @@ impl Rope {
         &self,
         line_break_idx: usize,
     ) -> Option<(&str, usize, usize, usize)> {
-        some_if!(
-            line_break_idx <= self.len_lines() => self.rope.chunk_at_line_break(line_break_idx)
-        )
+        match self.line_breaks {
+            LineBreaks::Unicode => some_if!(
+                line_break_idx <= self.len_lines() => self.rope.chunk_at_line_break(line_break_idx)
+            ),
+            _ => self.chunk_at_char(self.line_to_char(line_break_idx)?),
+        }
     }
 
     /// Returns `None` if the start of the range is greater than the end, or if the
@@ -211,7 +325,7 @@ impl Rope {
         let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
 
         some_if!(
-            start <= end || end <= self.len_chars() => self.rope.slice(char_range)
+            start <= end && end <= self.len_chars() => self.rope.slice(char_range)
         )
     }
 
@@ -226,8 +340,11 @@ impl Rope {
     }
 
     #[inline]
-    pub fn lines(&self) -> ropey::iter::Lines {
-        self.rope.lines()
+    pub fn lines(&self) -> Lines {
+        Lines {
+            rope: self,
+            line_idx: 0,
+        }
     }
 
     #[inline]
@@ -236,6 +353,42 @@ impl Rope {
     }
 }
 
+/// An iterator over the lines of a `Rope`, split according to its `LineBreaks`.
+pub struct Lines<'rope> {
+    rope: &'rope Rope,
+    line_idx: usize,
+}
+
+impl<'rope> Iterator for Lines<'rope> {
+    type Item = ropey::RopeSlice<'rope>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.rope.line(self.line_idx)?;
+        self.line_idx += 1;
+        Some(line)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.line_idx = self.line_idx.saturating_add(n);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.rope.len_lines().saturating_sub(self.line_idx);
+        (remaining, Some(remaining))
+    }
+}
+
+/// The char that stands in for `c` in `Rope::line_index`. `ropey` splits lines at CR, LF and
+/// CRLF, along with the other Unicode line breaks, so we keep the CRs and LFs that break lines
+/// and nothing else.
+fn line_index_char(line_breaks: LineBreaks, c: char) -> char {
+    match (line_breaks, c) {
+        (_, '\n') | (LineBreaks::CROrLF, '\r') => c,
+        _ => ' ',
+    }
+}
+
 impl<'a> From<&'a str> for Rope {
     #[inline]
     fn from(text: &'a str) -> Self {
@@ -267,7 +420,11 @@ impl From<Rope> for ropey::Rope {
 impl From<ropey::Rope> for Rope {
     #[inline]
     fn from(rope: ropey::Rope) -> Self {
-        Rope { rope }
+        Rope {
+            rope,
+            line_breaks: d!(),
+            line_index: d!(),
+        }
     }
 }
 
@@ -425,3 +582,6 @@ fn end_bound_to_num(b: Bound<&usize>) -> Option<usize> {
         Bound::Unbounded => None,
     }
 }
+
+#[cfg(test)]
+mod tests;
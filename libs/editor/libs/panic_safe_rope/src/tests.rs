@@ -0,0 +1,151 @@
+use super::*;
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn remove_returns_none_for_backwards_ranges_instead_of_panicking() {
+    let mut rope = Rope::from_str("abcde");
+
+    assert_eq!(rope.remove(3..2), None);
+    assert_eq!(rope.remove(3..=1), None);
+    assert_eq!(rope.to_string(), "abcde");
+}
+
+#[test]
+fn remove_returns_none_for_ranges_past_the_end_instead_of_panicking() {
+    let mut rope = Rope::from_str("abcde");
+
+    assert_eq!(rope.remove(2..6), None);
+    assert_eq!(rope.remove(6..), None);
+    assert_eq!(rope.to_string(), "abcde");
+}
+
+#[test]
+fn remove_removes_ranges_within_the_text() {
+    let mut rope = Rope::from_str("abcde");
+
+    assert_eq!(rope.remove(1..3), Some(()));
+    assert_eq!(rope.to_string(), "ade");
+    assert_eq!(rope.remove(3..), Some(()));
+    assert_eq!(rope.remove(..), Some(()));
+    assert_eq!(rope.to_string(), "");
+}
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn slice_returns_none_for_ranges_that_are_backwards_or_past_the_end() {
+    let rope = Rope::from_str("abcde");
+
+    assert!(rope.slice(3..2).is_none());
+    assert!(rope.slice(2..6).is_none());
+    assert!(rope.slice(6..).is_none());
+    assert_eq!(
+        rope.slice(1..3).map(|s| s.to_string()),
+        Some("bc".to_owned())
+    );
+    assert_eq!(rope.slice(5..).map(|s| s.to_string()), Some("".to_owned()));
+}
+
+/// The char index just after each line break, found without using `ropey`'s line index.
+fn naive_line_starts(text: &str, line_breaks: LineBreaks) -> Vec<usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = Vec::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let breaks = match (line_breaks, c) {
+            (_, '\n') => true,
+            (LineBreaks::CROrLF, '\r') => chars.get(i + 1) != Some(&'\n'),
+            _ => false,
+        };
+        if breaks {
+            output.push(i + 1);
+        }
+    }
+    output
+}
+
+fn assert_lines_match(rope: &Rope, line_breaks: LineBreaks) {
+    let starts = naive_line_starts(&rope.to_string(), line_breaks);
+
+    assert_eq!(rope.len_lines(), starts.len() + 1);
+    for (line, &start) in starts.iter().enumerate() {
+        assert_eq!(rope.line_to_char(line + 1), Some(start));
+        assert_eq!(rope.char_to_line(start), Some(line + 1));
+        assert_eq!(rope.char_to_line(start - 1), Some(line));
+    }
+}
+
+#[test]
+fn edits_near_the_start_of_a_large_buffer_keep_the_lines_right() {
+    for &line_breaks in &[LineBreaks::LFOnly, LineBreaks::CROrLF] {
+        let mut rope = Rope::from_str(&"a\r\nb\rc\u{2028}d\n".repeat(20_000));
+        rope.set_line_breaks(line_breaks);
+
+        for i in 0..2_000 {
+            match i % 4 {
+                0 => rope.insert(i % 7, "\r\nx\r").unwrap(),
+                1 => rope.insert_char(i % 5, '\n').unwrap(),
+                2 => rope.remove(i % 3..i % 3 + 4).unwrap(),
+                _ => rope.insert_char(1, '\r').unwrap(),
+            }
+        }
+
+        assert_lines_match(&rope, line_breaks);
+    }
+}
+
+#[test]
+fn splitting_and_appending_keep_the_lines_right() {
+    for &line_breaks in &[LineBreaks::LFOnly, LineBreaks::CROrLF] {
+        let mut rope = Rope::from_str("a\r\nb\rc\u{2028}d\n");
+        rope.set_line_breaks(line_breaks);
+
+        // This splits the CRLF.
+        let other = rope.split_off(2).unwrap();
+        assert_lines_match(&rope, line_breaks);
+        assert_lines_match(&other, line_breaks);
+
+        rope.append(other);
+        assert_lines_match(&rope, line_breaks);
+    }
+}
+
+#[test]
+fn line_len_chars_counts_line_breaks_the_way_each_mode_splits_lines() {
+    let text = "a\r\nbc\rd\u{2028}e\n";
+    let line_lens = |line_breaks| {
+        let mut rope = Rope::from_str(text);
+        rope.set_line_breaks(line_breaks);
+        (0..=rope.len_lines())
+            .map(|line| rope.line_len_chars(line))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        line_lens(LineBreaks::LFOnly),
+        vec![Some(3), Some(7), Some(0), None]
+    );
+    assert_eq!(
+        line_lens(LineBreaks::CROrLF),
+        vec![Some(3), Some(3), Some(4), Some(0), None]
+    );
+    assert_eq!(
+        line_lens(LineBreaks::Unicode),
+        vec![Some(3), Some(3), Some(2), Some(2), Some(0), None]
+    );
+}
+
+#[test]
+fn line_len_chars_of_the_last_line_has_no_line_break_to_count() {
+    for &line_breaks in &[LineBreaks::LFOnly, LineBreaks::CROrLF, LineBreaks::Unicode] {
+        let mut rope = Rope::from_str("ab\ncd");
+        rope.set_line_breaks(line_breaks);
+
+        assert_eq!(rope.line_len_chars(1), Some(2));
+        assert_eq!(rope.line_len_chars(2), None);
+
+        let mut rope = Rope::new();
+        rope.set_line_breaks(line_breaks);
+
+        assert_eq!(rope.line_len_chars(0), Some(0));
+        assert_eq!(rope.line_len_chars(1), None);
+    }
+}
@@ -190,3 +190,956 @@ fn insertion_with_forward_selection_deletes_selected_text() {
     assert_eq!(c.highlight_position, None);
     assert_eq!(c.position, pos! {l 0 o 3});
 }
+
+#[test]
+fn undo_reverts_a_run_of_typed_characters_in_one_step() {
+    let mut buffer: TextBuffer = d!();
+    buffer.insert('a');
+    buffer.insert('b');
+    buffer.insert('c');
+
+    buffer.undo();
+
+    let s: String = (&buffer).into();
+    assert_eq!(s, "");
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 0});
+}
+
+#[test]
+fn moving_the_cursor_ends_the_run_of_typed_characters() {
+    let mut buffer: TextBuffer = d!();
+    buffer.insert('a');
+    buffer.insert('b');
+    buffer.move_cursor(0, Move::Left);
+    buffer.insert('c');
+
+    buffer.undo();
+
+    let s: String = (&buffer).into();
+    assert_eq!(s, "ab");
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 1});
+}
+
+#[test]
+fn undo_then_redo_of_a_selection_deletion_restores_both_states() {
+    let mut buffer: TextBuffer = "1234".into();
+    buffer.move_cursor(0, Move::Right);
+    buffer.extend_selection(0, Move::Right);
+    buffer.extend_selection(0, Move::Right);
+    let cursors_before = buffer.cursors.clone();
+
+    buffer.delete();
+    let cursors_after = buffer.cursors.clone();
+
+    buffer.undo();
+    assert_eq!(String::from(&buffer), "1234");
+    assert_eq!(buffer.cursors, cursors_before);
+
+    buffer.redo();
+    assert_eq!(String::from(&buffer), "14");
+    assert_eq!(buffer.cursors, cursors_after);
+}
+
+#[test]
+fn a_multi_cursor_insert_is_a_single_undo_step() {
+    let mut buffer: TextBuffer = "ab\ncd".into();
+    buffer.cursors = Vec1::try_from_vec(vec![
+        Cursor::new(pos! {l 0 o 1}),
+        Cursor::new(pos! {l 1 o 1}),
+    ])
+    .unwrap();
+
+    buffer.insert('x');
+    assert_eq!(String::from(&buffer), "axb\ncxd");
+
+    buffer.undo();
+    assert_eq!(String::from(&buffer), "ab\ncd");
+    assert_eq!(buffer.cursors.len(), 2);
+}
+
+#[test]
+fn editing_after_an_undo_discards_the_redo_steps() {
+    let mut buffer: TextBuffer = d!();
+    buffer.insert('a');
+    buffer.undo();
+    buffer.insert('b');
+
+    buffer.redo();
+
+    assert_eq!(String::from(&buffer), "b");
+}
+
+#[test]
+fn pasting_one_line_per_cursor_gives_each_cursor_its_own_line() {
+    let mut buffer: TextBuffer = "ab\ncd".into();
+    buffer.cursors = Vec1::try_from_vec(vec![
+        Cursor::new(pos! {l 0 o 1}),
+        Cursor::new(pos! {l 1 o 1}),
+    ])
+    .unwrap();
+
+    buffer.insert_string("x\ny".to_owned());
+
+    assert_eq!(String::from(&buffer), "axb\ncyd");
+}
+
+#[test]
+fn cutting_a_selection_returns_and_removes_the_selected_text() {
+    let mut buffer: TextBuffer = "1234".into();
+    buffer.move_cursor(0, Move::Right);
+    buffer.extend_selection(0, Move::Right);
+    buffer.extend_selection(0, Move::Right);
+
+    assert_eq!(buffer.cut_selections(), vec!["23".to_owned()]);
+    assert_eq!(String::from(&buffer), "14");
+}
+
+#[test]
+fn moving_by_words_skips_punctuation_and_whitespace() {
+    let mut buffer: TextBuffer = "let foo_bar = baz(1.5);".into();
+
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 3});
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 11});
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 17});
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 21});
+
+    buffer.move_cursor(0, Move::ToPreviousWordStart);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 18});
+    buffer.move_cursor(0, Move::ToPreviousWordStart);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 14});
+}
+
+#[test]
+fn moving_by_words_crosses_line_breaks() {
+    let mut buffer: TextBuffer = "ab\r\ncd".into();
+
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 2});
+    buffer.move_cursor(0, Move::ToNextWordEnd);
+    assert_eq!(buffer.cursors.first().position, pos! {l 1 o 0});
+    buffer.move_cursor(0, Move::ToPreviousWordStart);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 2});
+}
+
+#[test]
+fn extending_the_selection_by_words_selects_the_word() {
+    let mut buffer: TextBuffer = "one two".into();
+    buffer.extend_selection(0, Move::ToNextWordEnd);
+
+    assert_eq!(buffer.copy_selections(), vec!["one".to_owned()]);
+}
+
+#[test]
+fn moving_right_and_left_steps_over_whole_grapheme_clusters() {
+    let mut buffer: TextBuffer = "o\u{308}👨\u{200d}👩x".into();
+
+    buffer.move_cursor(0, Move::Right);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 2});
+    buffer.move_cursor(0, Move::Right);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 5});
+
+    buffer.move_cursor(0, Move::Left);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 2});
+    buffer.move_cursor(0, Move::Left);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 0});
+}
+
+#[test]
+fn extending_the_selection_right_selects_whole_grapheme_clusters() {
+    let mut buffer: TextBuffer = "o\u{308}x".into();
+    buffer.extend_selection(0, Move::Right);
+
+    assert_eq!(buffer.copy_selections(), vec!["o\u{308}".to_owned()]);
+}
+
+#[test]
+fn deleting_removes_a_whole_grapheme_cluster() {
+    let mut buffer: TextBuffer = "ao\u{308}".into();
+    buffer.move_cursor(0, Move::ToLineEnd);
+
+    buffer.delete();
+
+    assert_eq!(String::from(&buffer), "a");
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 1});
+}
+
+#[test]
+fn deleting_at_the_start_of_a_line_removes_the_whole_crlf() {
+    let mut buffer: TextBuffer = "a\r\nb".into();
+    buffer.move_cursor(0, Move::Down);
+    buffer.move_cursor(0, Move::ToLineStart);
+
+    buffer.delete();
+
+    assert_eq!(String::from(&buffer), "ab");
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 1});
+}
+
+#[test]
+fn nth_word_start_counts_words_across_lines() {
+    let buffer: TextBuffer = "one, two\n\n  three".into();
+
+    assert_eq!(buffer.nth_word_start(0), Some(pos! {l 0 o 0}));
+    assert_eq!(buffer.nth_word_start(1), Some(pos! {l 0 o 5}));
+    assert_eq!(buffer.nth_word_start(2), Some(pos! {l 2 o 2}));
+    assert_eq!(buffer.nth_word_start(3), None);
+}
+
+#[test]
+fn find_all_finds_non_overlapping_matches_across_lines() {
+    let buffer: TextBuffer = "aaaa\nbaa".into();
+
+    assert_eq!(
+        buffer.find_all("aa"),
+        vec![
+            (pos! {l 0 o 0}, pos! {l 0 o 2}),
+            (pos! {l 0 o 2}, pos! {l 0 o 4}),
+            (pos! {l 1 o 1}, pos! {l 1 o 3}),
+        ]
+    );
+    assert_eq!(
+        buffer.find_all("a\nb"),
+        vec![(pos! {l 0 o 3}, pos! {l 1 o 1})]
+    );
+}
+
+#[test]
+fn find_all_finds_matches_that_straddle_rope_chunks() {
+    let mut s = "xy".repeat(5000);
+    s.push_str("needle");
+    s.push_str(&"xy".repeat(5000));
+    let buffer: TextBuffer = s.as_str().into();
+    assert!(buffer.rope.chunks().count() > 1);
+
+    assert_eq!(buffer.find_all("yxyx").len(), s.matches("yxyx").count());
+
+    assert_eq!(
+        buffer.find_all("needle"),
+        vec![(pos! {l 0 o 10000}, pos! {l 0 o 10006})]
+    );
+}
+
+#[test]
+fn regex_replace_all_expands_capture_groups_across_line_breaks() {
+    let mut buffer: TextBuffer = "a1\r\nb2\nc3".into();
+
+    assert_eq!(
+        buffer.regex_replace_all(r"(\d)\r?\n(\w)", "$2$1").unwrap(),
+        2
+    );
+
+    assert_eq!(String::from(&buffer), "ab1c23");
+}
+
+#[test]
+fn regex_replace_all_is_a_single_undo_step() {
+    let mut buffer: TextBuffer = "x y x".into();
+
+    buffer.regex_replace_all("x", "zz").unwrap();
+    assert_eq!(String::from(&buffer), "zz y zz");
+
+    buffer.undo();
+    assert_eq!(String::from(&buffer), "x y x");
+}
+
+#[test]
+fn regex_replace_all_keeps_cursors_next_to_the_same_text() {
+    let mut buffer: TextBuffer = "aa b aa c".into();
+    buffer.cursors = Vec1::try_from_vec(vec![
+        Cursor::new(pos! {l 0 o 3}),
+        Cursor::new(pos! {l 0 o 9}),
+        Cursor::new(pos! {l 0 o 6}),
+    ])
+    .unwrap();
+
+    buffer.regex_replace_all("aa", "a").unwrap();
+
+    assert_eq!(String::from(&buffer), "a b a c");
+    let positions: Vec<_> = buffer.cursors.iter().map(|c| c.position).collect();
+    assert_eq!(
+        positions,
+        vec![pos! {l 0 o 2}, pos! {l 0 o 7}, pos! {l 0 o 4}]
+    );
+}
+
+#[test]
+fn regex_replace_all_reports_invalid_patterns_without_changing_anything() {
+    let mut buffer: TextBuffer = "(".into();
+
+    assert!(buffer.regex_replace_all("(", "").is_err());
+    assert!(buffer.regex_find_all("(").is_err());
+    assert_eq!(String::from(&buffer), "(");
+}
+
+#[test]
+fn adding_cursors_at_next_occurrences_selects_the_word_then_each_match_in_turn() {
+    let mut buffer: TextBuffer = "foo bar foo\nfoo".into();
+    buffer.move_cursor(0, Move::Down);
+
+    buffer.add_cursor_at_next_occurrence();
+    assert_eq!(buffer.copy_selections(), vec!["foo".to_owned()]);
+
+    buffer.add_cursor_at_next_occurrence();
+    buffer.add_cursor_at_next_occurrence();
+    // Every occurrence is taken at this point, so this should do nothing.
+    buffer.add_cursor_at_next_occurrence();
+
+    let selections: Vec<_> = buffer
+        .cursors
+        .iter()
+        .map(|c| (c.highlight_position, c.position))
+        .collect();
+    assert_eq!(
+        selections,
+        vec![
+            (Some(pos! {l 1 o 0}), pos! {l 1 o 3}),
+            (Some(pos! {l 0 o 0}), pos! {l 0 o 3}),
+            (Some(pos! {l 0 o 8}), pos! {l 0 o 11}),
+        ]
+    );
+}
+
+#[test]
+fn selecting_all_occurrences_makes_a_cursor_for_each_match() {
+    let mut buffer: TextBuffer = "ab ab\nab".into();
+    buffer.extend_selection(0, Move::Right);
+    buffer.extend_selection(0, Move::Right);
+
+    buffer.select_all_occurrences();
+
+    let positions: Vec<_> = buffer.cursors.iter().map(|c| c.position).collect();
+    assert_eq!(
+        positions,
+        vec![pos! {l 0 o 2}, pos! {l 0 o 5}, pos! {l 1 o 2}]
+    );
+    assert_eq!(buffer.copy_selections(), vec!["ab"; 3]);
+}
+
+#[test]
+fn selecting_all_occurrences_without_a_selection_uses_the_word_under_the_cursor() {
+    let mut buffer: TextBuffer = "foo bar foo\nfoo".into();
+    buffer.move_cursor(0, Move::Down);
+    buffer.move_cursor(0, Move::Right);
+
+    buffer.select_all_occurrences();
+
+    let selections: Vec<_> = buffer
+        .cursors
+        .iter()
+        .map(|c| (c.highlight_position, c.position))
+        .collect();
+    assert_eq!(
+        selections,
+        vec![
+            (Some(pos! {l 0 o 0}), pos! {l 0 o 3}),
+            (Some(pos! {l 0 o 8}), pos! {l 0 o 11}),
+            (Some(pos! {l 1 o 0}), pos! {l 1 o 3}),
+        ]
+    );
+}
+
+#[test]
+fn selecting_all_occurrences_without_a_selection_or_a_word_does_nothing() {
+    let mut buffer: TextBuffer = "  ".into();
+    buffer.move_cursor(0, Move::Right);
+
+    buffer.select_all_occurrences();
+
+    assert_eq!(buffer.cursors.len(), 1);
+    assert_eq!(buffer.cursors.first().highlight_position, None);
+}
+
+#[test]
+fn adding_cursors_on_adjacent_lines_keeps_the_sticky_offset() {
+    let mut buffer: TextBuffer = "abcd\na\nabcd\nabcd".into();
+    buffer.move_cursor(0, Move::Down);
+    buffer.move_cursor(0, Move::Down);
+    for _ in 0..3 {
+        buffer.move_cursor(0, Move::Right);
+    }
+
+    buffer.add_cursor_on_adjacent_line(true);
+    buffer.add_cursor_on_adjacent_line(true);
+    buffer.add_cursor_on_adjacent_line(true);
+    buffer.add_cursor_on_adjacent_line(false);
+    // There is no line below the last one, so this should do nothing.
+    buffer.add_cursor_on_adjacent_line(false);
+
+    let lines: Vec<_> = buffer.cursors.iter().map(|c| c.position.line).collect();
+    assert_eq!(lines, vec![2, 1, 0, 3]);
+    // The short line only has room for the cursor at its end, but the line above still gets the
+    // original offset.
+    assert_eq!(buffer.cursors[1].position, pos! {l 1 o 1});
+    assert_eq!(buffer.cursors[2].position, pos! {l 0 o 3});
+    assert_eq!(buffer.cursors[3].position, pos! {l 3 o 3});
+
+    buffer.collapse_cursors();
+    let positions: Vec<_> = buffer.cursors.iter().map(|c| c.position).collect();
+    assert_eq!(positions, vec![pos! {l 2 o 3}]);
+}
+
+#[test]
+fn adding_a_cursor_where_there_already_is_one_does_nothing() {
+    let mut buffer: TextBuffer = "abc".into();
+
+    buffer.add_cursor(pos! {l 0 o 2});
+    buffer.add_cursor(pos! {l 0 o 2});
+    buffer.add_cursor(pos! {l 0 o 0});
+
+    assert_eq!(buffer.cursors.len(), 2);
+}
+
+#[test]
+fn a_cursor_that_grows_while_merging_merges_with_ones_it_was_already_compared_with() {
+    let mut buffer: TextBuffer = "abcdefg".into();
+    let selection = |position, highlight| {
+        let mut cursor = Cursor::new(position);
+        cursor.highlight_position = Some(highlight);
+        cursor
+    };
+    buffer.cursors = Vec1::try_from_vec(vec![
+        selection(pos! {l 0 o 2}, pos! {l 0 o 0}),
+        selection(pos! {l 0 o 6}, pos! {l 0 o 4}),
+        selection(pos! {l 0 o 5}, pos! {l 0 o 1}),
+    ])
+    .unwrap();
+
+    buffer.merge_overlapping_cursors();
+
+    let cursors: Vec<_> = buffer
+        .cursors
+        .iter()
+        .map(|c| (c.position, c.highlight_position))
+        .collect();
+    assert_eq!(cursors, vec![(pos! {l 0 o 6}, Some(pos! {l 0 o 0}))]);
+}
+
+#[derive(Clone, Debug)]
+enum MultiCursorEdit {
+    Insert(char),
+    Delete,
+    Cut,
+}
+
+fn arb_multi_cursor_edit() -> impl Strategy<Value = MultiCursorEdit> {
+    prop_oneof![
+        "[ab\r\n]".prop_map(|s| MultiCursorEdit::Insert(s.chars().next().unwrap())),
+        Just(MultiCursorEdit::Delete),
+        Just(MultiCursorEdit::Cut),
+    ]
+}
+
+/// Some text, where the only multi-char graphemes are CRLFs, and cursors given as char offsets
+/// into it, which may be in the middle of a CRLF.
+fn arb_text_and_cursor_offsets() -> impl Strategy<Value = (String, Vec<(usize, Option<usize>)>)> {
+    "[ab\r\n]{0,16}".prop_flat_map(|s: String| {
+        let len = s.chars().count();
+        (
+            Just(s),
+            proptest::collection::vec((0..=len, proptest::option::of(0..=len)), 1..6),
+        )
+    })
+}
+
+/// A deliberately simple model of a multi-cursor buffer, working directly on a `String` and char
+/// offsets into it, one cursor at a time.
+#[derive(Debug)]
+struct NaiveBuffer {
+    text: String,
+    cursors: Vec<(usize, Option<usize>)>,
+}
+
+impl NaiveBuffer {
+    fn bounds((position, highlight): (usize, Option<usize>)) -> (usize, usize) {
+        let highlight = highlight.unwrap_or(position);
+        (
+            std::cmp::min(position, highlight),
+            std::cmp::max(position, highlight),
+        )
+    }
+
+    /// Each cursor claims the chars it selects, along with the spots between them. A cursor
+    /// without a selection claims just the spot it is on. Cursors that claim the same thing,
+    /// directly or through other cursors, become one cursor covering all of them, which keeps
+    /// the place in the list, and the direction, of the first of them.
+    fn merge(&mut self) {
+        let len = self.text.chars().count();
+        let mut group: Vec<usize> = (0..self.cursors.len()).collect();
+        fn root(group: &[usize], mut i: usize) -> usize {
+            while group[i] != i {
+                i = group[i];
+            }
+            i
+        }
+
+        // Chars are claimed at even indexes, and the spots before them at odd ones.
+        let mut claimed_by: Vec<Option<usize>> = vec![None; 2 * len + 2];
+        for (i, &cursor) in self.cursors.iter().enumerate() {
+            let (start, end) = Self::bounds(cursor);
+            let claims = if start == end {
+                2 * start + 1..2 * start + 2
+            } else {
+                2 * start + 2..2 * end + 1
+            };
+            for claim in claims {
+                match claimed_by[claim] {
+                    Some(other) => {
+                        let (a, b) = (root(&group, other), root(&group, i));
+                        group[std::cmp::max(a, b)] = std::cmp::min(a, b);
+                    }
+                    None => claimed_by[claim] = Some(i),
+                }
+            }
+        }
+
+        let mut merged = Vec::new();
+        for first in 0..self.cursors.len() {
+            if root(&group, first) != first {
+                continue;
+            }
+            let members: Vec<_> = (first..self.cursors.len())
+                .filter(|&i| root(&group, i) == first)
+                .map(|i| self.cursors[i])
+                .collect();
+            if members.len() == 1 {
+                merged.push(members[0]);
+                continue;
+            }
+
+            let start = members.iter().map(|&c| Self::bounds(c).0).min().unwrap();
+            let end = members.iter().map(|&c| Self::bounds(c).1).max().unwrap();
+            let (position, highlight) = self.cursors[first];
+            merged.push(if start == end {
+                (start, None)
+            } else if highlight.map_or(false, |h| h > position) {
+                (start, Some(end))
+            } else {
+                (end, Some(start))
+            });
+        }
+        self.cursors = merged;
+    }
+
+    fn ends_with_crlf(&self, offset: usize) -> bool {
+        self.text
+            .chars()
+            .take(offset)
+            .skip(offset - 2)
+            .eq("\r\n".chars())
+    }
+
+    fn apply(&mut self, edit: &MultiCursorEdit) {
+        self.merge();
+        for i in 0..self.cursors.len() {
+            let (o, highlight) = self.cursors[i];
+            let (start, end) = Self::bounds(self.cursors[i]);
+            let (start, end, inserted) = match (edit, highlight) {
+                (MultiCursorEdit::Insert(c), _) => (start, end, c.to_string()),
+                (MultiCursorEdit::Delete, Some(h)) if h != o => (start, end, String::new()),
+                // A CRLF is one grapheme, so it is deleted all at once.
+                (MultiCursorEdit::Delete, _) if o >= 2 && self.ends_with_crlf(o) => {
+                    (o - 2, o, String::new())
+                }
+                (MultiCursorEdit::Delete, _) if o > 0 => (o - 1, o, String::new()),
+                (MultiCursorEdit::Cut, Some(_)) if start != end => (start, end, String::new()),
+                _ => continue,
+            };
+
+            let mut chars: Vec<char> = self.text.chars().collect();
+            chars.splice(start..end, inserted.chars());
+            self.text = chars.into_iter().collect();
+
+            let len = inserted.chars().count();
+            let shift = |x: usize| {
+                if x >= end {
+                    x - (end - start) + len
+                } else if x > start {
+                    start
+                } else {
+                    x
+                }
+            };
+            for (j, cursor) in self.cursors.iter_mut().enumerate() {
+                *cursor = if j == i {
+                    (start + len, None)
+                } else {
+                    (shift(cursor.0), cursor.1.map(shift))
+                };
+            }
+        }
+        self.merge();
+    }
+}
+
+fn cursor_offsets(buffer: &TextBuffer) -> Vec<(usize, Option<usize>)> {
+    buffer
+        .cursors
+        .iter()
+        .map(|c| match offset_pair(&buffer.rope, c) {
+            (Some(o), highlight) => (o.0, highlight.map(|h| h.0)),
+            _ => unreachable!("cursor out of bounds: {:?}", c),
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn multi_cursor_edits_match_the_naive_model(
+        (text, offsets) in arb_text_and_cursor_offsets(),
+        edits in proptest::collection::vec(arb_multi_cursor_edit(), 1..8),
+    ) {
+        let mut buffer: TextBuffer = text.as_str().into();
+        let to_pos = |o: usize| char_offset_to_pos(&buffer.rope, &AbsoluteCharOffset(o)).unwrap();
+        let cursors: Vec<Cursor> = offsets
+            .iter()
+            .map(|&(o, highlight)| {
+                let mut cursor = Cursor::new(to_pos(o));
+                cursor.highlight_position = highlight.map(to_pos);
+                cursor
+            })
+            .collect();
+        buffer.cursors = Vec1::try_from_vec(cursors).unwrap();
+        let mut model = NaiveBuffer { text: text.clone(), cursors: offsets };
+
+        for edit in &edits {
+            match edit {
+                MultiCursorEdit::Insert(c) => buffer.insert(*c),
+                MultiCursorEdit::Delete => buffer.delete(),
+                MultiCursorEdit::Cut => {
+                    buffer.cut_selections();
+                }
+            }
+            model.apply(edit);
+
+            prop_assert_eq!(String::from(&buffer), model.text.clone());
+            prop_assert_eq!(cursor_offsets(&buffer), model.cursors.clone());
+        }
+
+        for _ in &edits {
+            buffer.undo();
+        }
+        prop_assert_eq!(String::from(&buffer), text);
+    }
+
+    #[test]
+    fn multi_cursor_edits_leave_no_overlapping_cursors(
+        (text, offsets) in arb_text_and_cursor_offsets(),
+        c in "[ab\r\n]",
+    ) {
+        let mut buffer: TextBuffer = text.as_str().into();
+        let to_pos = |o: usize| char_offset_to_pos(&buffer.rope, &AbsoluteCharOffset(o)).unwrap();
+        let cursors: Vec<Cursor> = offsets
+            .iter()
+            .map(|&(o, highlight)| {
+                let mut cursor = Cursor::new(to_pos(o));
+                cursor.highlight_position = highlight.map(to_pos);
+                cursor
+            })
+            .collect();
+        buffer.cursors = Vec1::try_from_vec(cursors).unwrap();
+
+        buffer.insert_string(c);
+
+        let mut bounds: Vec<_> = buffer.cursors.iter().map(cursor_bounds).collect();
+        bounds.sort();
+        for pair in bounds.windows(2) {
+            prop_assert!(pair[0].1 <= pair[1].0, "{:?} overlaps {:?}", pair[0], pair[1]);
+            prop_assert!(pair[0] != pair[1]);
+        }
+    }
+}
+
+#[test]
+fn selecting_a_word_or_line_at_a_position_replaces_the_cursors() {
+    let mut buffer: TextBuffer = "one two\nthree".into();
+    buffer.add_cursor(pos! {l 1 o 1});
+
+    buffer.select_word_at(pos! {l 0 o 5});
+    assert_eq!(buffer.copy_selections(), vec!["two".to_owned()]);
+
+    buffer.select_line_at(pos! {l 0 o 5});
+    assert_eq!(buffer.copy_selections(), vec!["one two\n".to_owned()]);
+
+    buffer.select_line_at(pos! {l 1 o 0});
+    assert_eq!(buffer.copy_selections(), vec!["three".to_owned()]);
+}
+
+#[test]
+fn dragging_the_cursor_selects_from_where_the_drag_started() {
+    let mut buffer: TextBuffer = "one two\nthree".into();
+    buffer.cursors = Vec1::new(Cursor::new(pos! {l 0 o 4}));
+
+    buffer.drag_cursor_to(pos! {l 1 o 2});
+    buffer.drag_cursor_to(pos! {l 0 o 1});
+
+    assert_eq!(buffer.copy_selections(), vec!["ne ".to_owned()]);
+}
+
+#[test]
+fn deleting_to_a_move_removes_the_text_each_cursor_would_move_over() {
+    let mut buffer: TextBuffer = "one two\nthree four".into();
+    buffer.cursors = Vec1::new(Cursor::new(pos! {l 0 o 4}));
+    buffer.add_cursor(pos! {l 1 o 6});
+
+    buffer.delete_to(Move::ToNextWordEnd);
+    assert_eq!(String::from(&buffer), "one \nthree ");
+
+    buffer.delete_to(Move::ToPreviousWordStart);
+    assert_eq!(String::from(&buffer), "\n");
+}
+
+#[test]
+fn deleting_to_the_line_end_keeps_the_line_break_and_selections_win() {
+    let mut buffer: TextBuffer = "abc\r\ndef".into();
+    buffer.cursors = Vec1::new(Cursor::new(pos! {l 0 o 1}));
+    buffer.add_cursor(pos! {l 1 o 2});
+    buffer.extend_selection(1, Move::Left);
+
+    buffer.delete_to(Move::ToLineEnd);
+    assert_eq!(String::from(&buffer), "a\r\ndf");
+
+    // The CRLF goes away in one piece.
+    buffer.delete_to(Move::Right);
+    assert_eq!(String::from(&buffer), "ad");
+}
+
+#[test]
+fn line_endings_are_detected_when_loading() {
+    let line_endings = |s: &str| TextBuffer::from(s).line_endings();
+
+    assert_eq!(line_endings(""), LineEndings::Uniform(LineEnding::LF));
+    assert_eq!(
+        line_endings("a\r\nb\r\n"),
+        LineEndings::Uniform(LineEnding::CRLF)
+    );
+    assert_eq!(line_endings("a\rb"), LineEndings::Uniform(LineEnding::CR));
+    assert_eq!(
+        line_endings("a\r\nb\nc\r\n\r"),
+        LineEndings::Mixed(LineEnding::CRLF)
+    );
+}
+
+#[test]
+fn line_breaks_are_inserted_in_the_buffers_own_style() {
+    let mut buffer: TextBuffer = "a\r\nb".into();
+    buffer.move_cursor(0, Move::ToBufferEnd);
+
+    buffer.insert_line_break();
+
+    assert_eq!(String::from(&buffer), "a\r\nb\r\n");
+    assert_eq!(buffer.cursors.first().position, pos! {l 2 o 0});
+}
+
+#[test]
+fn converting_line_endings_only_changes_the_selection_if_there_is_one() {
+    let mut buffer: TextBuffer = "a\nb\r\nc\rd".into();
+    buffer.move_cursor(0, Move::Down);
+    buffer.extend_selection(0, Move::Down);
+
+    assert_eq!(buffer.convert_line_endings(LineEnding::LF), 1);
+    assert_eq!(String::from(&buffer), "a\nb\nc\rd");
+    assert_eq!(buffer.line_endings(), LineEndings::Mixed(LineEnding::LF));
+
+    buffer.move_cursor(0, Move::Right);
+    assert_eq!(buffer.convert_line_endings(LineEnding::CRLF), 3);
+    assert_eq!(String::from(&buffer), "a\r\nb\r\nc\r\nd");
+    assert_eq!(
+        buffer.line_endings(),
+        LineEndings::Uniform(LineEnding::CRLF)
+    );
+
+    buffer.undo();
+    assert_eq!(String::from(&buffer), "a\nb\nc\rd");
+}
+
+proptest! {
+    #[test]
+    fn line_ending_counts_stay_accurate_through_edits(
+        (text, offsets) in arb_text_and_cursor_offsets(),
+        edits in proptest::collection::vec(("[\r\n]{1,2}", any::<bool>()), 1..8),
+    ) {
+        let mut buffer: TextBuffer = text.as_str().into();
+        let to_pos = |o: usize| char_offset_to_pos(&buffer.rope, &AbsoluteCharOffset(o)).unwrap();
+        let cursors: Vec<Cursor> = offsets
+            .iter()
+            .map(|&(o, highlight)| {
+                let mut cursor = Cursor::new(to_pos(o));
+                cursor.highlight_position = highlight.map(to_pos);
+                cursor
+            })
+            .collect();
+        buffer.cursors = Vec1::try_from_vec(cursors).unwrap();
+
+        for (inserted, delete) in edits {
+            if delete {
+                buffer.delete();
+            } else {
+                buffer.insert_string(inserted);
+            }
+
+            let expected = TextBuffer::from(String::from(&buffer)).line_ending_counts;
+            prop_assert_eq!(buffer.line_ending_counts, expected);
+        }
+
+        buffer.undo();
+        let expected = TextBuffer::from(String::from(&buffer)).line_ending_counts;
+        prop_assert_eq!(buffer.line_ending_counts, expected);
+    }
+}
+
+#[test]
+fn only_the_chosen_line_breaks_split_lines() {
+    let mut rope = r!("a\u{b}b\rc\r\nd\ne");
+    assert_eq!(rope.len_lines(), 5);
+
+    rope.set_line_breaks(LineBreaks::CROrLF);
+    assert_eq!(rope.len_lines(), 4);
+    assert_eq!(
+        char_offset_to_pos(&rope, &AbsoluteCharOffset(4)),
+        Some(pos! {l 1 o 0})
+    );
+
+    rope.set_line_breaks(LineBreaks::LFOnly);
+    assert_eq!(rope.len_lines(), 3);
+    assert_eq!(
+        char_offset_to_pos(&rope, &AbsoluteCharOffset(4)),
+        Some(pos! {l 0 o 4})
+    );
+    assert_eq!(
+        pos_to_char_offset(&rope, &pos! {l 1 o 1}),
+        Some(AbsoluteCharOffset(8))
+    );
+}
+
+#[test]
+fn changing_line_breaks_keeps_the_cursors_and_history_on_the_same_chars() {
+    let mut buffer: TextBuffer = "a\rb\nc".into();
+    buffer.move_cursor(0, Move::ToBufferEnd);
+    buffer.insert('d');
+    buffer.move_cursor(0, Move::Left);
+    assert_eq!(buffer.cursors.first().position, pos! {l 2 o 1});
+
+    buffer.set_line_breaks(LineBreaks::LFOnly);
+    assert_eq!(buffer.cursors.first().position, pos! {l 1 o 1});
+
+    buffer.undo();
+    assert_eq!(String::from(&buffer), "a\rb\nc");
+    assert_eq!(buffer.cursors.first().position, pos! {l 1 o 1});
+
+    buffer.redo();
+    assert_eq!(buffer.cursors.first().position, pos! {l 1 o 2});
+}
+
+/// The start of each line after the first, found the slow and obvious way.
+fn naive_line_starts(s: &str, line_breaks: LineBreaks) -> Vec<usize> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('\r', Some('\n')) if line_breaks == LineBreaks::CROrLF => {
+                i += 1;
+                output.push(i + 1);
+            }
+            ('\r', _) if line_breaks == LineBreaks::CROrLF => output.push(i + 1),
+            ('\n', _) => output.push(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    output
+}
+
+proptest! {
+    #[test]
+    fn line_queries_match_the_naive_split_through_edits(
+        s in "[a\r\n]{0,16}",
+        edits in proptest::collection::vec((any::<bool>(), any::<usize>(), "[a\r\n]{0,3}"), 0..8),
+        lf_only in any::<bool>(),
+    ) {
+        let line_breaks = if lf_only { LineBreaks::LFOnly } else { LineBreaks::CROrLF };
+        let mut rope = r!(s);
+        rope.set_line_breaks(line_breaks);
+        let mut text: Vec<char> = s.chars().collect();
+
+        for (remove, at, inserted) in edits {
+            let at = at % (text.len() + 1);
+            if remove {
+                let end = std::cmp::min(at + inserted.chars().count(), text.len());
+                rope.remove(at..end);
+                text.drain(at..end);
+            } else {
+                rope.insert(at, &inserted);
+                text.splice(at..at, inserted.chars());
+            }
+
+            let text: String = text.iter().collect();
+            let expected = naive_line_starts(&text, line_breaks);
+            prop_assert_eq!(rope.len_lines(), expected.len() + 1);
+            for (line, &start) in expected.iter().enumerate() {
+                prop_assert_eq!(rope.line_to_char(line + 1), Some(start));
+                prop_assert_eq!(rope.char_to_line(start), Some(line + 1));
+            }
+        }
+    }
+}
+
+#[test]
+fn text_window_only_has_the_chars_in_the_given_lines_and_columns() {
+    let buffer: TextBuffer = "abcd\r\nef\n\nghijk".into();
+
+    assert_eq!(
+        buffer.text_window(0..2, 1..3),
+        ("bcf\n".to_owned(), vec![2])
+    );
+    assert_eq!(
+        buffer.text_window(0..2, 3..10),
+        ("d\r\n".to_owned(), vec![3])
+    );
+    assert_eq!(
+        buffer.text_window(1..10, 0..10),
+        ("ef\n\nghijk".to_owned(), vec![3, 4])
+    );
+    assert_eq!(buffer.text_window(4..10, 0..10), (String::new(), vec![]));
+}
+
+#[test]
+fn moving_to_the_line_end_stops_before_the_line_break() {
+    let mut buffer: TextBuffer = "ab\r\ncd\nef".into();
+    buffer.cursors = Vec1::new(Cursor::new(pos! {l 0 o 1}));
+    buffer.add_cursor(pos! {l 1 o 0});
+
+    buffer.move_all_cursors(Move::ToLineEnd);
+    let positions: Vec<_> = buffer.cursors.iter().map(|c| c.position).collect();
+    assert_eq!(positions, vec![pos! {l 0 o 2}, pos! {l 1 o 2}]);
+
+    // Otherwise, typing at the end of a line would put the text at the start of the next one.
+    buffer.insert('!');
+    assert_eq!(String::from(&buffer), "ab!\r\ncd!\nef");
+}
+
+#[test]
+fn positions_past_the_end_of_a_line_do_not_land_in_its_line_break() {
+    let mut buffer: TextBuffer = "ab\r\ncd\nefgh".into();
+
+    assert!(in_bounds(&buffer.rope, pos! {l 0 o 2}));
+    assert!(!in_bounds(&buffer.rope, pos! {l 0 o 3}));
+    assert!(!in_bounds(&buffer.rope, pos! {l 1 o 3}));
+    assert_eq!(
+        nearest_valid_position_on_same_line(&buffer.rope, &pos! {l 0 o 3}),
+        Some(pos! {l 0 o 2})
+    );
+
+    buffer.cursors = Vec1::new(Cursor::new(pos! {l 2 o 4}));
+    buffer.move_all_cursors(Move::Up);
+    assert_eq!(buffer.cursors.first().position, pos! {l 1 o 2});
+    buffer.move_all_cursors(Move::Up);
+    assert_eq!(buffer.cursors.first().position, pos! {l 0 o 2});
+
+    buffer.insert('!');
+    assert_eq!(String::from(&buffer), "ab!\r\ncd\nefgh");
+}
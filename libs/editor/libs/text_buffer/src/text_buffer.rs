@@ -1,13 +1,285 @@
 use editor_types::{ByteIndex, Cursor, MultiCursorBuffer, Vec1};
 use macros::{borrow, borrow_mut, d};
+pub use panic_safe_rope::LineBreaks;
 use panic_safe_rope::Rope;
 use platform_types::{AbsoluteCharOffset, CharOffset, Move, Position};
+use regex::Regex;
 use std::borrow::Borrow;
+use std::ops::Range;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 
 #[derive(Default)]
 pub struct TextBuffer {
     rope: Rope,
     cursors: Vec1<Cursor>,
+    history: History,
+    line_ending_counts: LineEndingCounts,
+}
+
+/// One of the kinds of line break a buffer can use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    LF,
+    CRLF,
+    CR,
+}
+
+d!(for LineEnding: LineEnding::LF);
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::LF => "\n",
+            LineEnding::CRLF => "\r\n",
+            LineEnding::CR => "\r",
+        }
+    }
+
+    /// The inverse of the `Display` impl, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "LF" => Some(LineEnding::LF),
+            "CRLF" => Some(LineEnding::CRLF),
+            "CR" => Some(LineEnding::CR),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            LineEnding::LF => "LF",
+            LineEnding::CRLF => "CRLF",
+            LineEnding::CR => "CR",
+        })
+    }
+}
+
+/// Which kinds of line break a buffer has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Every line break is of this kind. Buffers without any line breaks count as `LF`.
+    Uniform(LineEnding),
+    /// There is more than one kind of line break, and this is the most common one.
+    Mixed(LineEnding),
+}
+
+impl LineEndings {
+    /// The kind of line break that new lines should get.
+    pub fn preferred(self) -> LineEnding {
+        match self {
+            LineEndings::Uniform(ending) | LineEndings::Mixed(ending) => ending,
+        }
+    }
+}
+
+impl std::fmt::Display for LineEndings {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LineEndings::Uniform(ending) => write!(f, "{}", ending),
+            LineEndings::Mixed(ending) => write!(f, "Mixed ({})", ending),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct LineEndingCounts {
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+}
+
+impl LineEndingCounts {
+    fn add(&mut self, ending: LineEnding) {
+        match ending {
+            LineEnding::LF => self.lf += 1,
+            LineEnding::CRLF => self.crlf += 1,
+            LineEnding::CR => self.cr += 1,
+        }
+    }
+
+    fn remove(&mut self, ending: LineEnding) {
+        match ending {
+            LineEnding::LF => self.lf -= 1,
+            LineEnding::CRLF => self.crlf -= 1,
+            LineEnding::CR => self.cr -= 1,
+        }
+    }
+
+    fn line_endings(self) -> LineEndings {
+        let counts = [
+            (self.lf, LineEnding::LF),
+            (self.crlf, LineEnding::CRLF),
+            (self.cr, LineEnding::CR),
+        ];
+        let kinds = counts.iter().filter(|(count, _)| *count > 0).count();
+        // `max_by_key` returns the last maximum, so going backwards makes LF win ties.
+        let most_common = counts
+            .iter()
+            .rev()
+            .max_by_key(|(count, _)| *count)
+            .map_or(d!(), |&(_, ending)| ending);
+
+        if kinds > 1 {
+            LineEndings::Mixed(most_common)
+        } else {
+            LineEndings::Uniform(most_common)
+        }
+    }
+}
+
+/// The line breaks that start in `start..end`, along with where they start. A CRLF counts as
+/// starting at its CR, even if its LF is past `end`.
+fn line_breaks_starting_in(rope: &Rope, start: usize, end: usize) -> Vec<(usize, LineEnding)> {
+    let len = rope.len_chars();
+    let end = std::cmp::min(end, len);
+    let mut output = Vec::new();
+    if start >= end {
+        return output;
+    }
+
+    // Include a char on either side, so line breaks on the edges can be told apart.
+    let from = start.saturating_sub(1);
+    let chars: Vec<char> = rope
+        .slice(from..std::cmp::min(end + 1, len))
+        .map(|s| s.chars().collect())
+        .unwrap_or_default();
+    let char_at = |i: usize| chars.get(i.checked_sub(from)?).cloned();
+
+    for i in start..end {
+        match char_at(i) {
+            Some('\r') if char_at(i + 1) == Some('\n') => output.push((i, LineEnding::CRLF)),
+            Some('\r') => output.push((i, LineEnding::CR)),
+            Some('\n') if i == 0 || char_at(i - 1) != Some('\r') => {
+                output.push((i, LineEnding::LF))
+            }
+            _ => {}
+        }
+    }
+
+    output
+}
+
+/// A single primitive modification of the rope. `offset` is where `text` starts, both before a
+/// removal and after an insertion.
+#[derive(Clone, Debug)]
+enum Change {
+    Insert {
+        offset: AbsoluteCharOffset,
+        text: String,
+    },
+    Remove {
+        offset: AbsoluteCharOffset,
+        text: String,
+    },
+}
+
+impl Change {
+    fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::Insert { offset, text } => Change::Remove { offset, text },
+            Change::Remove { offset, text } => Change::Insert { offset, text },
+        }
+    }
+}
+
+fn apply_change(rope: &mut Rope, counts: &mut LineEndingCounts, change: &Change) {
+    let (offset, removed, inserted) = match change {
+        Change::Insert { offset, text } => (offset.0, 0, text.chars().count()),
+        Change::Remove { offset, text } => (offset.0, text.chars().count(), 0),
+    };
+    // Besides the changed text itself, only the line breaks right next to it can change kind.
+    let window_start = offset.saturating_sub(1);
+
+    for (_, ending) in line_breaks_starting_in(rope, window_start, offset + removed + 1) {
+        counts.remove(ending);
+    }
+    match change {
+        Change::Insert { offset, text } => {
+            rope.insert(offset.0, text);
+        }
+        Change::Remove { offset, text } => {
+            rope.remove(offset.0..offset.0 + text.chars().count());
+        }
+    }
+    for (_, ending) in line_breaks_starting_in(rope, window_start, offset + inserted + 1) {
+        counts.add(ending);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EditKind {
+    /// Inserting a character that did not replace a selection. Consecutive edits of this kind
+    /// are undone together.
+    Typing,
+    Other,
+}
+
+/// Everything that one undo or redo step needs: the changes, applied in order, and the cursors
+/// from either side of them.
+#[derive(Debug)]
+struct Edit {
+    kind: EditKind,
+    changes: Vec<Change>,
+    cursors_before: Vec1<Cursor>,
+    cursors_after: Vec1<Cursor>,
+    /// The version of the text after this edit. This is filled in by `History::record`.
+    version: Version,
+}
+
+/// Identifies the text in a buffer as it was at some point. Any edit gives the text a new
+/// version, and undoing or redoing edits brings back the version from before, so comparing
+/// versions tells you whether the text has changed since, say, it was last saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Version(usize);
+
+#[derive(Default, Debug)]
+struct History {
+    edits: Vec<Edit>,
+    /// `edits[..index]` are currently applied and `edits[index..]` can be redone.
+    index: usize,
+    /// Whether the next `EditKind::Typing` edit can join the most recent edit.
+    typing_run_open: bool,
+    /// The most recent version given out.
+    last_version: Version,
+}
+
+impl History {
+    fn record(&mut self, mut edit: Edit) {
+        if edit.changes.is_empty() {
+            return;
+        }
+        let kind = edit.kind;
+        self.last_version = Version(self.last_version.0 + 1);
+        edit.version = self.last_version;
+
+        self.edits.truncate(self.index);
+        match self.edits.last_mut() {
+            Some(last)
+                if self.typing_run_open
+                    && kind == EditKind::Typing
+                    && last.kind == EditKind::Typing
+                    && last.cursors_after == edit.cursors_before =>
+            {
+                last.changes.extend(edit.changes);
+                last.cursors_after = edit.cursors_after;
+                last.version = edit.version;
+            }
+            _ => {
+                self.edits.push(edit);
+            }
+        }
+        self.index = self.edits.len();
+        self.typing_run_open = kind == EditKind::Typing;
+    }
+
+    fn version(&self) -> Version {
+        match self.index.checked_sub(1).and_then(|i| self.edits.get(i)) {
+            Some(edit) => edit.version,
+            None => d!(),
+        }
+    }
 }
 
 impl From<String> for TextBuffer {
@@ -15,6 +287,7 @@ impl From<String> for TextBuffer {
         let mut output: Self = d!();
 
         output.rope = Rope::from(s);
+        output.count_line_endings();
 
         output
     }
@@ -25,6 +298,7 @@ impl From<&str> for TextBuffer {
         let mut output: Self = d!();
 
         output.rope = Rope::from(s);
+        output.count_line_endings();
 
         output
     }
@@ -45,52 +319,287 @@ fn offset_pair(
     )
 }
 
+/// Applies `change` to `rope` and remembers it so it can be undone later.
+fn record_change(
+    rope: &mut Rope,
+    counts: &mut LineEndingCounts,
+    changes: &mut Vec<Change>,
+    change: Change,
+) {
+    apply_change(rope, counts, &change);
+    changes.push(change);
+}
+
+fn removal(rope: &Rope, min: AbsoluteCharOffset, max: AbsoluteCharOffset) -> Change {
+    Change::Remove {
+        offset: min,
+        text: rope
+            .slice(min.0..max.0)
+            .map(|s| s.chars().collect())
+            .unwrap_or_default(),
+    }
+}
+
+impl TextBuffer {
+    /// Inserts `text_for(i)` at the `i`th cursor, replacing that cursor's selection if it has
+    /// one, and records all of it as a single edit.
+    fn insert_per_cursor<F>(&mut self, mut kind: EditKind, text_for: F)
+    where
+        F: Fn(usize) -> String,
+    {
+        self.merge_overlapping_cursors();
+        let rope = &self.rope;
+        if self
+            .cursors
+            .iter()
+            .any(|c| selection_range(rope, c).map_or(false, |(min, max)| min < max))
+        {
+            kind = EditKind::Other;
+        }
+
+        self.edit_per_cursor(kind, |_, i, (o, highlight)| {
+            let (min, max) = match highlight {
+                Some(h) => (std::cmp::min(o, h), std::cmp::max(o, h)),
+                None => (o, o),
+            };
+            Some((min, max, text_for(i)))
+        });
+    }
+
+    /// Calls `edit_for` with each cursor's index and its position and highlight offsets, in
+    /// order. When it returns `Some((start, end, text))`, `text` replaces `start..end` and that
+    /// cursor ends up just after `text` with nothing selected. Every other cursor is shifted so
+    /// it keeps pointing at the same text, cursors that end up overlapping are merged, and all of
+    /// it is recorded as a single edit.
+    fn edit_per_cursor<F>(&mut self, kind: EditKind, mut edit_for: F)
+    where
+        F: FnMut(
+            &Rope,
+            usize,
+            (AbsoluteCharOffset, Option<AbsoluteCharOffset>),
+        ) -> Option<(AbsoluteCharOffset, AbsoluteCharOffset, String)>,
+    {
+        let cursors_before = self.cursors.clone();
+        let mut changes = Vec::with_capacity(self.cursors.len());
+
+        let mut offsets: Vec<_> = self
+            .cursors
+            .iter()
+            .map(|c| offset_pair(&self.rope, c))
+            .collect();
+
+        for i in 0..offsets.len() {
+            let (start, end, text) = match offsets[i] {
+                (Some(o), highlight) => match edit_for(&self.rope, i, (o, highlight)) {
+                    Some(edit) => edit,
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if start < end {
+                let change = removal(&self.rope, start, end);
+                record_change(
+                    &mut self.rope,
+                    &mut self.line_ending_counts,
+                    &mut changes,
+                    change,
+                );
+            }
+            let len = text.chars().count();
+            if len > 0 {
+                record_change(
+                    &mut self.rope,
+                    &mut self.line_ending_counts,
+                    &mut changes,
+                    Change::Insert {
+                        offset: start,
+                        text: text.clone(),
+                    },
+                );
+            }
+
+            let replacements = [Replacement { start, end, text }];
+            for (j, (position, highlight)) in offsets.iter_mut().enumerate() {
+                if j == i {
+                    *position = Some(AbsoluteCharOffset(start.0 + len));
+                    *highlight = None;
+                } else {
+                    *position = position.map(|o| shift_offset(&replacements, o));
+                    *highlight = highlight.map(|o| shift_offset(&replacements, o));
+                }
+            }
+        }
+
+        let rope = &self.rope;
+        for (cursor, (position, highlight)) in self.cursors.iter_mut().zip(offsets) {
+            if let Some(p) = position.and_then(|o| char_offset_to_pos(rope, &o)) {
+                if p != cursor.position {
+                    cursor.position = p;
+                    cursor.sticky_offset = p.offset;
+                }
+            }
+            cursor.highlight_position = highlight.and_then(|o| char_offset_to_pos(rope, &o));
+        }
+        self.merge_overlapping_cursors();
+
+        self.history.record(Edit {
+            kind,
+            changes,
+            cursors_before,
+            cursors_after: self.cursors.clone(),
+            version: d!(),
+        });
+    }
+
+    /// Folds each cursor that overlaps an earlier one, or sits in the same place, into that
+    /// earlier one, which grows to cover both selections.
+    fn merge_overlapping_cursors(&mut self) {
+        // A cursor that grows can come to overlap one it was already compared with, so we keep
+        // going until nothing merges.
+        while self.merge_overlapping_cursors_once() {}
+    }
+
+    /// Returns whether any cursors were merged.
+    fn merge_overlapping_cursors_once(&mut self) -> bool {
+        let mut merged: Vec<Cursor> = Vec::with_capacity(self.cursors.len());
+
+        for cursor in self.cursors.iter() {
+            let (start, end) = cursor_bounds(cursor);
+            let overlapping = merged.iter_mut().find(|c| {
+                let (s, e) = cursor_bounds(c);
+                (s, e) == (start, end) || (s < end && start < e)
+            });
+
+            match overlapping {
+                Some(c) => {
+                    let (s, e) = cursor_bounds(c);
+                    let (s, e) = (std::cmp::min(s, start), std::cmp::max(e, end));
+                    let backwards = c.highlight_position.map_or(false, |h| h > c.position);
+                    if s == e {
+                        c.highlight_position = None;
+                    } else if backwards {
+                        c.position = s;
+                        c.highlight_position = Some(e);
+                    } else {
+                        c.position = e;
+                        c.highlight_position = Some(s);
+                    }
+                }
+                None => merged.push(cursor.clone()),
+            }
+        }
+
+        if merged.len() == self.cursors.len() {
+            return false;
+        }
+
+        if let Ok(cursors) = Vec1::try_from_vec(merged) {
+            self.cursors = cursors;
+        }
+        true
+    }
+}
+
+/// The earliest and latest positions that `cursor` touches.
+fn cursor_bounds(cursor: &Cursor) -> (Position, Position) {
+    let highlight = cursor.highlight_position.unwrap_or(cursor.position);
+    (
+        std::cmp::min(cursor.position, highlight),
+        std::cmp::max(cursor.position, highlight),
+    )
+}
+
 impl MultiCursorBuffer for TextBuffer {
     #[perf_viz::record]
     fn insert(&mut self, ch: char) {
-        for cursor in &mut self.cursors {
-            match offset_pair(&self.rope, cursor) {
-                (Some(AbsoluteCharOffset(o)), highlight)
-                    if highlight.is_none() || Some(AbsoluteCharOffset(o)) == highlight =>
-                {
-                    self.rope.insert_char(o, ch);
-                    move_right(&self.rope, cursor);
-                }
+        let kind = if ch == '\n' {
+            EditKind::Other
+        } else {
+            EditKind::Typing
+        };
+
+        self.insert_per_cursor(kind, |_| ch.to_string());
+    }
+
+    #[perf_viz::record]
+    fn insert_string(&mut self, s: String) {
+        let lines: Vec<&str> = s.lines().collect();
+
+        if lines.len() > 1 && lines.len() == self.cursors.len() {
+            self.insert_per_cursor(EditKind::Other, |i| lines[i].to_owned());
+        } else {
+            self.insert_per_cursor(EditKind::Other, |_| s.clone());
+        }
+    }
+
+    #[perf_viz::record]
+    fn copy_selections(&self) -> Vec<String> {
+        self.cursors
+            .iter()
+            .map(|cursor| match offset_pair(&self.rope, cursor) {
                 (Some(o1), Some(o2)) => {
                     let min = std::cmp::min(o1, o2);
                     let max = std::cmp::max(o1, o2);
+                    self.rope
+                        .slice(min.0..max.0)
+                        .map(|s| s.chars().collect())
+                        .unwrap_or_default()
+                }
+                _ => d!(),
+            })
+            .collect()
+    }
 
-                    self.rope.remove(min.0..max.0);
-                    cursor.position = char_offset_to_pos(&self.rope, &min).unwrap_or_default();
-                    cursor.highlight_position = None;
+    #[perf_viz::record]
+    fn cut_selections(&mut self) -> Vec<String> {
+        self.merge_overlapping_cursors();
+        let mut output = vec![String::new(); self.cursors.len()];
 
-                    self.rope.insert_char(min.0, ch);
-                    move_right(&self.rope, cursor);
+        self.edit_per_cursor(EditKind::Other, |rope, i, offsets| match offsets {
+            (o1, Some(o2)) if o1 != o2 => {
+                let min = std::cmp::min(o1, o2);
+                let max = std::cmp::max(o1, o2);
+
+                if let Change::Remove { text, .. } = removal(rope, min, max) {
+                    output[i] = text;
                 }
-                _ => {}
+                Some((min, max, String::new()))
             }
-        }
+            _ => None,
+        });
+
+        output
     }
 
     #[perf_viz::record]
     fn delete(&mut self) {
-        for cursor in &mut self.cursors {
-            match offset_pair(&self.rope, cursor) {
-                (Some(AbsoluteCharOffset(o)), None) if o > 0 => {
-                    self.rope.remove((o - 1)..o);
-                    move_left(&self.rope, cursor);
-                }
-                (Some(o1), Some(o2)) if o1 > 0 || o2 > 0 => {
-                    let min = std::cmp::min(o1, o2);
-                    let max = std::cmp::max(o1, o2);
+        self.delete_to(Move::Left);
+    }
 
-                    self.rope.remove(dbg!(min.0..max.0));
-                    cursor.position = char_offset_to_pos(&self.rope, &min).unwrap_or_default();
-                    cursor.highlight_position = None;
+    #[perf_viz::record]
+    fn delete_to(&mut self, r#move: Move) {
+        self.merge_overlapping_cursors();
+
+        self.edit_per_cursor(EditKind::Other, |rope, _, offsets| {
+            let (start, end) = match offsets {
+                (o1, Some(o2)) if o1 != o2 => (std::cmp::min(o1, o2), std::cmp::max(o1, o2)),
+                (o, _) => {
+                    // Moving left or right steps over whole grapheme clusters, so accented
+                    // letters, emoji sequences and CRLFs go away in one piece.
+                    let mut cursor = Cursor::new(char_offset_to_pos(rope, &o)?);
+                    move_cursor_directly(rope, &mut cursor, r#move);
+                    let target = pos_to_char_offset(rope, &cursor.position)?;
+                    (std::cmp::min(o, target), std::cmp::max(o, target))
                 }
-                _ => {}
+            };
+
+            if start == end {
+                None
+            } else {
+                Some((start, end, String::new()))
             }
-        }
+        });
     }
 
     #[perf_viz::record]
@@ -98,8 +607,16 @@ impl MultiCursorBuffer for TextBuffer {
         if let Some(cursor) = self.cursors.get_mut(index) {
             if let Some(p) = cursor.highlight_position {
                 let decreasing = match r#move {
-                    Move::Up | Move::Left | Move::ToLineStart | Move::ToBufferStart => true,
-                    Move::Down | Move::Right | Move::ToLineEnd | Move::ToBufferEnd => false,
+                    Move::Up
+                    | Move::Left
+                    | Move::ToLineStart
+                    | Move::ToBufferStart
+                    | Move::ToPreviousWordStart => true,
+                    Move::Down
+                    | Move::Right
+                    | Move::ToLineEnd
+                    | Move::ToBufferEnd
+                    | Move::ToNextWordEnd => false,
                 };
                 cursor.highlight_position = None;
                 if (decreasing && p <= cursor.position) || (!decreasing && p >= cursor.position) {
@@ -121,6 +638,11 @@ impl MultiCursorBuffer for TextBuffer {
         }
     }
 
+    #[perf_viz::record]
+    fn in_bounds<P: Borrow<Position>>(&self, position: P) -> bool {
+        in_bounds(&self.rope, position)
+    }
+
     #[perf_viz::record]
     fn find_index<P: Borrow<Position>>(&self, p: P) -> Option<ByteIndex> {
         find_index(&self.rope, p)
@@ -134,16 +656,20 @@ impl MultiCursorBuffer for TextBuffer {
 }
 
 fn nearest_valid_position_on_same_line(rope: &Rope, p: &Position) -> Option<Position> {
-    let line = rope.lines().nth(p.line)?;
+    let count = nth_line_text_count(rope, p.line)?;
 
     Some(Position {
-        offset: std::cmp::min(p.offset, CharOffset(line.len_chars())),
+        offset: std::cmp::min(p.offset, count),
         ..*p
     })
 }
 
 fn in_bounds<P: Borrow<Position>>(rope: &Rope, position: P) -> bool {
-    find_index(rope, position) != None
+    let position = position.borrow();
+    // `find_index` alone would accept offsets that run past the end of the line, into its line
+    // break or onto later lines.
+    nth_line_text_count(rope, position.line).map_or(false, |count| position.offset <= count)
+        && find_index(rope, position) != None
 }
 
 fn find_index<P: Borrow<Position>>(rope: &Rope, p: P) -> Option<ByteIndex> {
@@ -152,14 +678,17 @@ fn find_index<P: Borrow<Position>>(rope: &Rope, p: P) -> Option<ByteIndex> {
 }
 
 fn nth_line_count(rope: &Rope, n: usize) -> Option<CharOffset> {
-    rope.lines().nth(n).map(|l| CharOffset(l.len_chars()))
+    rope.line_len_chars(n).map(CharOffset)
+}
+
+/// Like `nth_line_count`, but without counting the line break.
+fn nth_line_text_count(rope: &Rope, n: usize) -> Option<CharOffset> {
+    line_text(rope, n).map(|text| CharOffset(text.chars().count()))
 }
 
 fn last_line_index_and_count(rope: &Rope) -> Option<(usize, CharOffset)> {
-    rope.lines()
-        .map(|l| CharOffset(l.len_chars()))
-        .enumerate()
-        .last()
+    let last_index = rope.len_lines().checked_sub(1)?;
+    nth_line_count(rope, last_index).map(|count| (last_index, count))
 }
 
 #[perf_viz::record]
@@ -176,10 +705,10 @@ fn char_offset_to_pos(
     if rope.len_chars() == offset {
         Some(rope.len_lines() - 1)
     } else {
-        dbg!(rope.char_to_line(offset))
+        rope.char_to_line(offset)
     }
     .and_then(|line_index| {
-        let start_of_line = dbg!(rope.line_to_char(line_index))?;
+        let start_of_line = rope.line_to_char(line_index)?;
 
         offset.checked_sub(start_of_line).map(|o| Position {
             line: line_index,
@@ -203,6 +732,8 @@ fn move_cursor_directly(rope: &Rope, cursor: &mut Cursor, r#move: Move) {
         Move::ToLineEnd => move_to_line_end(rope, cursor),
         Move::ToBufferStart => move_to_rope_start(rope, cursor),
         Move::ToBufferEnd => move_to_rope_end(rope, cursor),
+        Move::ToPreviousWordStart => move_to_previous_word_start(rope, cursor),
+        Move::ToNextWordEnd => move_to_next_word_end(rope, cursor),
     }
 }
 
@@ -226,7 +757,7 @@ fn move_to_with_fallback(rope: &Rope, cursor: &mut Cursor, new_position: Positio
     let target_line = new_position.line;
     if let Moved::No = move_to(rope, cursor, new_position) {
         let mut target_offset = d!();
-        if let Some(count) = nth_line_count(rope, target_line) {
+        if let Some(count) = nth_line_text_count(rope, target_line) {
             target_offset = count;
         }
         move_to(
@@ -293,7 +824,8 @@ fn move_to_line_start(rope: &Rope, cursor: &mut Cursor) {
 #[perf_viz::record]
 fn move_to_line_end(rope: &Rope, cursor: &mut Cursor) {
     let line = cursor.position.line;
-    if let Some(offset) = nth_line_count(rope, line) {
+    // Stop before the line break, rather than after it at what is really the next line's start.
+    if let Some(offset) = nth_line_text_count(rope, line) {
         let new_position = Position { line, offset };
         move_to(rope, cursor, new_position);
     }
@@ -311,6 +843,144 @@ fn move_to_rope_end(rope: &Rope, cursor: &mut Cursor) {
     }
 }
 
+/// The `min` and `max` of the cursor's selection, if it has one.
+fn selection_range(
+    rope: &Rope,
+    cursor: &Cursor,
+) -> Option<(AbsoluteCharOffset, AbsoluteCharOffset)> {
+    match offset_pair(rope, cursor) {
+        (Some(o1), Some(o2)) => Some((std::cmp::min(o1, o2), std::cmp::max(o1, o2))),
+        _ => None,
+    }
+}
+
+/// A cursor at `end` with everything from `start` selected.
+fn selecting(rope: &Rope, start: AbsoluteCharOffset, end: AbsoluteCharOffset) -> Option<Cursor> {
+    let mut cursor = Cursor::new(char_offset_to_pos(rope, &end)?);
+    cursor.highlight_position = Some(char_offset_to_pos(rope, &start)?);
+    Some(cursor)
+}
+
+/// Selects the word the cursor is in, or the one that ends right before it. Returns whether
+/// there was such a word.
+fn select_word(rope: &Rope, cursor: &mut Cursor) -> bool {
+    let Position { line, offset } = cursor.position;
+    let text = match line_text(rope, line) {
+        Some(text) => text,
+        None => return false,
+    };
+    let byte_offset = char_offset_to_byte(&text, offset);
+
+    let word = text
+        .split_word_bound_indices()
+        .filter(|&(_, s)| is_word(s))
+        .map(|(i, s)| (i, i + s.len()))
+        .find(|&(start, end)| start <= byte_offset && byte_offset <= end);
+
+    match word {
+        Some((start, end)) => {
+            let to_position = |byte_index: usize| Position {
+                line,
+                offset: CharOffset(text[..byte_index].chars().count()),
+            };
+            cursor.highlight_position = Some(to_position(start));
+            cursor.position = to_position(end);
+            cursor.sticky_offset = cursor.position.offset;
+            true
+        }
+        None => false,
+    }
+}
+
+fn is_line_break(rope: &Rope, c: char) -> bool {
+    match c {
+        '\n' | '\r' => true,
+        '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}' => {
+            rope.line_breaks() == LineBreaks::Unicode
+        }
+        _ => false,
+    }
+}
+
+/// Returns the text of the given line without its line break.
+fn line_text(rope: &Rope, line: usize) -> Option<String> {
+    let mut text = String::from(rope.line(line)?);
+    let len = text.trim_end_matches(|c| is_line_break(rope, c)).len();
+    text.truncate(len);
+    Some(text)
+}
+
+fn char_offset_to_byte(text: &str, CharOffset(offset): CharOffset) -> usize {
+    text.char_indices()
+        .nth(offset)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| text.len())
+}
+
+/// Word boundaries also separate runs of whitespace and punctuation, which we don't want to stop
+/// at, so we only count segments with something alphanumeric in them as words.
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+#[perf_viz::record]
+fn move_to_previous_word_start(rope: &Rope, cursor: &mut Cursor) {
+    let Position { line, offset } = cursor.position;
+    if offset == 0 {
+        if line > 0 {
+            let line = line - 1;
+            if let Some(text) = line_text(rope, line) {
+                let offset = CharOffset(text.chars().count());
+                move_to(rope, cursor, Position { line, offset });
+            }
+        }
+        return;
+    }
+
+    if let Some(text) = line_text(rope, line) {
+        let byte_offset = char_offset_to_byte(&text, offset);
+        let start = text
+            .split_word_bound_indices()
+            .take_while(|&(i, _)| i < byte_offset)
+            .filter(|&(_, s)| is_word(s))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        let offset = CharOffset(text[..start].chars().count());
+        move_to(rope, cursor, Position { line, offset });
+    }
+}
+
+#[perf_viz::record]
+fn move_to_next_word_end(rope: &Rope, cursor: &mut Cursor) {
+    let Position { line, offset } = cursor.position;
+    if let Some(text) = line_text(rope, line) {
+        if offset >= text.chars().count() {
+            move_to(
+                rope,
+                cursor,
+                Position {
+                    line: line + 1,
+                    offset: d!(),
+                },
+            );
+            return;
+        }
+
+        let byte_offset = char_offset_to_byte(&text, offset);
+        let end = text
+            .split_word_bound_indices()
+            .map(|(i, s)| (i + s.len(), s))
+            .find(|&(end, s)| end > byte_offset && is_word(s))
+            .map(|(end, _)| end)
+            .unwrap_or_else(|| text.len());
+
+        let offset = CharOffset(text[..end].chars().count());
+        move_to(rope, cursor, Position { line, offset });
+    }
+}
+
 fn set_selection_to_here_if_not_set(cursor: &mut Cursor) {
     if cursor.highlight_position.is_none() {
         cursor.highlight_position = Some(cursor.position);
@@ -323,6 +993,608 @@ impl<'rope> TextBuffer {
     }
 }
 
+impl TextBuffer {
+    /// The chars in the given columns of the given lines, line breaks included, along with the
+    /// byte index in those chars where each line but the last ends. Lines past the end of the
+    /// buffer are left out. Only the returned chars are visited, so this stays cheap however
+    /// large the buffer is.
+    pub fn text_window(&self, lines: Range<usize>, columns: Range<usize>) -> (String, Vec<usize>) {
+        let rope = &self.rope;
+        let mut output = String::new();
+        let mut line_ends = Vec::new();
+
+        for line in lines.start..std::cmp::min(lines.end, rope.len_lines()) {
+            if line > lines.start {
+                line_ends.push(output.len());
+            }
+
+            let (start, end) = match rope.line_to_char(line) {
+                Some(start) => (
+                    start,
+                    rope.line_to_char(line + 1)
+                        .unwrap_or_else(|| rope.len_chars()),
+                ),
+                None => break,
+            };
+
+            let window_start = std::cmp::min(start.saturating_add(columns.start), end);
+            let window_end = std::cmp::min(start.saturating_add(columns.end), end);
+            if let Some(slice) = rope.slice(window_start..window_end) {
+                output.extend(slice.chars());
+            }
+        }
+
+        (output, line_ends)
+    }
+
+    /// Reverts the most recent edit that has not already been undone, including moving the
+    /// cursors back to where they were before it.
+    pub fn undo(&mut self) {
+        let history = &mut self.history;
+        if history.index == 0 {
+            return;
+        }
+        history.index -= 1;
+        history.typing_run_open = false;
+
+        if let Some(edit) = history.edits.get(history.index) {
+            for change in edit.changes.iter().rev() {
+                apply_change(
+                    &mut self.rope,
+                    &mut self.line_ending_counts,
+                    &change.inverse(),
+                );
+            }
+            self.cursors = edit.cursors_before.clone();
+        }
+    }
+
+    /// Reapplies the most recently undone edit, if there have been no edits since.
+    pub fn redo(&mut self) {
+        let history = &mut self.history;
+        if let Some(edit) = history.edits.get(history.index) {
+            for change in edit.changes.iter() {
+                apply_change(&mut self.rope, &mut self.line_ending_counts, change);
+            }
+            self.cursors = edit.cursors_after.clone();
+
+            history.index += 1;
+            history.typing_run_open = false;
+        }
+    }
+
+    /// The version of the text as it is now. See `Version`.
+    pub fn version(&self) -> Version {
+        self.history.version()
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn char_offset_to_pos(&self, offset: AbsoluteCharOffset) -> Option<Position> {
+        char_offset_to_pos(&self.rope, &offset)
+    }
+
+    /// Byte indexes in the middle of a char give the position before that char.
+    pub fn byte_index_to_pos(&self, ByteIndex(index): ByteIndex) -> Option<Position> {
+        self.rope
+            .byte_to_char(index)
+            .and_then(|offset| char_offset_to_pos(&self.rope, &AbsoluteCharOffset(offset)))
+    }
+
+    /// The start and end of every non-overlapping occurrence of `needle` in the buffer, in order.
+    pub fn find_all(&self, needle: &str) -> Vec<(Position, Position)> {
+        find_all(&self.rope, needle)
+            .into_iter()
+            .filter_map(|(start, end)| {
+                Some((
+                    char_offset_to_pos(&self.rope, &start)?,
+                    char_offset_to_pos(&self.rope, &end)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Adds a cursor at `position`, unless there is already one there.
+    pub fn add_cursor(&mut self, position: Position) {
+        if self.cursors.iter().all(|c| c.position != position) {
+            self.cursors.push(Cursor::new(position));
+        }
+    }
+
+    /// Adds a cursor on the line above the topmost cursor, or below the bottommost one, at that
+    /// cursor's sticky offset, or the end of the line if the line is too short.
+    pub fn add_cursor_on_adjacent_line(&mut self, upward: bool) {
+        let extreme = if upward {
+            self.cursors.iter().min_by_key(|c| c.position.line)
+        } else {
+            self.cursors.iter().max_by_key(|c| c.position.line)
+        };
+
+        let mut cursor = extreme.cloned().unwrap_or_default();
+        let line = cursor.position.line;
+        cursor.highlight_position = None;
+        if upward {
+            move_up(&self.rope, &mut cursor);
+        } else {
+            move_down(&self.rope, &mut cursor);
+        }
+
+        if cursor.position.line != line {
+            self.cursors.push(cursor);
+        }
+    }
+
+    /// Removes every cursor except the first one.
+    pub fn collapse_cursors(&mut self) {
+        self.cursors = Vec1::new(self.cursors.first().clone());
+    }
+
+    /// Replaces the cursors with one that selects from the first cursor's selection anchor, or
+    /// its position if it has no selection, to `position`.
+    pub fn drag_cursor_to(&mut self, position: Position) {
+        let mut cursor = self.cursors.first().clone();
+        if cursor.highlight_position.is_none() {
+            cursor.highlight_position = Some(cursor.position);
+        }
+        cursor.position = position;
+        cursor.sticky_offset = position.offset;
+
+        self.cursors = Vec1::new(cursor);
+    }
+
+    /// Replaces the cursors with one that selects the word at `position`, or just sits there if
+    /// there is no word.
+    pub fn select_word_at(&mut self, position: Position) {
+        let mut cursor = Cursor::new(position);
+        select_word(&self.rope, &mut cursor);
+
+        self.cursors = Vec1::new(cursor);
+    }
+
+    /// Replaces the cursors with one that selects the line `position` is on, along with its line
+    /// break.
+    pub fn select_line_at(&mut self, position: Position) {
+        let line = position.line;
+        let end = if line + 1 < self.rope.len_lines() {
+            Position {
+                line: line + 1,
+                offset: d!(),
+            }
+        } else {
+            Position {
+                line,
+                offset: nth_line_count(&self.rope, line).unwrap_or_default(),
+            }
+        };
+
+        let mut cursor = Cursor::new(end);
+        cursor.highlight_position = Some(Position { line, offset: d!() });
+
+        self.cursors = Vec1::new(cursor);
+    }
+
+    /// Adds a cursor selecting the next occurrence, after the newest cursor, of the newest
+    /// cursor's selected text, wrapping around to the start of the buffer if needed. If the
+    /// newest cursor has no selection, this selects the word it is on instead.
+    pub fn add_cursor_at_next_occurrence(&mut self) {
+        let needle = match self.occurrence_needle() {
+            // Selecting the word is all that should happen this time.
+            Some((_, true)) | None => return,
+            Some((needle, false)) => needle,
+        };
+
+        let after = match offset_pair(&self.rope, self.cursors.last()) {
+            (Some(o1), Some(o2)) => std::cmp::max(o1, o2),
+            (Some(o), None) => o,
+            _ => return,
+        };
+        let taken: Vec<_> = self
+            .cursors
+            .iter()
+            .map(|c| selection_range(&self.rope, c))
+            .collect();
+
+        let matches = find_all(&self.rope, &needle);
+        let next = matches
+            .iter()
+            .filter(|(start, _)| *start >= after)
+            .chain(matches.iter().filter(|(start, _)| *start < after))
+            .find(|&&(start, end)| !taken.contains(&Some((start, end))));
+
+        if let Some(&(start, end)) = next {
+            if let Some(cursor) = selecting(&self.rope, start, end) {
+                self.cursors.push(cursor);
+            }
+        }
+    }
+
+    /// Replaces the cursors with one selecting each occurrence of the newest cursor's selected
+    /// text, or of the word it is on if it has no selection.
+    pub fn select_all_occurrences(&mut self) {
+        let needle = match self.occurrence_needle() {
+            Some((needle, _)) => needle,
+            None => return,
+        };
+
+        let cursors: Vec<_> = find_all(&self.rope, &needle)
+            .into_iter()
+            .filter_map(|(start, end)| selecting(&self.rope, start, end))
+            .collect();
+
+        if let Ok(cursors) = Vec1::try_from_vec(cursors) {
+            self.cursors = cursors;
+        }
+    }
+
+    /// The text to look for occurrences of: the newest cursor's selected text, or, if it has no
+    /// selection, the word it is on, which then becomes its selection. The `bool` is whether the
+    /// word was just selected. `None` means there was no word to select.
+    fn occurrence_needle(&mut self) -> Option<(String, bool)> {
+        let rope = &self.rope;
+        let cursor = self.cursors.last_mut();
+        let just_selected = match selection_range(rope, cursor) {
+            Some((start, end)) if start != end => false,
+            _ => {
+                select_word(rope, cursor);
+                true
+            }
+        };
+
+        match selection_range(rope, cursor) {
+            Some((start, end)) if start != end => rope
+                .slice(start.0..end.0)
+                .map(|s| (s.chars().collect(), just_selected)),
+            _ => None,
+        }
+    }
+
+    /// The start and end of every match of the regular expression `pattern`. Line breaks are
+    /// ordinary characters as far as this is concerned, so patterns like `a\r?\nb` can match
+    /// across lines.
+    pub fn regex_find_all(&self, pattern: &str) -> Result<Vec<(Position, Position)>, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        let text = String::from(&self.rope);
+
+        Ok(regex
+            .find_iter(&text)
+            .filter_map(|m| {
+                let (start, end) = byte_range_to_char_range(&self.rope, m.start(), m.end())?;
+                Some((
+                    char_offset_to_pos(&self.rope, &start)?,
+                    char_offset_to_pos(&self.rope, &end)?,
+                ))
+            })
+            .collect())
+    }
+
+    /// Replaces every match of the regular expression `pattern` with `replacement`, where `$1`
+    /// or `${name}` in `replacement` refer to capture groups. This is recorded as a single edit.
+    /// Returns how many matches were replaced.
+    pub fn regex_replace_all(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+    ) -> Result<usize, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        let text = String::from(&self.rope);
+
+        let mut replacements = Vec::new();
+        for captures in regex.captures_iter(&text) {
+            let char_range = captures
+                .get(0)
+                .and_then(|m| byte_range_to_char_range(&self.rope, m.start(), m.end()));
+            if let Some((start, end)) = char_range {
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                replacements.push(Replacement {
+                    start,
+                    end,
+                    text: expanded,
+                });
+            }
+        }
+
+        self.replace_all(&replacements);
+
+        Ok(replacements.len())
+    }
+
+    pub fn line_breaks(&self) -> LineBreaks {
+        self.rope.line_breaks()
+    }
+
+    /// Changes which chars split the buffer into lines. Every cursor, including the ones kept
+    /// for undo and redo, stays on the same chars, even though their positions change.
+    pub fn set_line_breaks(&mut self, line_breaks: LineBreaks) {
+        if self.rope.line_breaks() == line_breaks {
+            return;
+        }
+
+        let mut old = self.rope.clone();
+        let mut new = self.rope.clone();
+        new.set_line_breaks(line_breaks);
+
+        fn remap_all(cursors: &mut Vec1<Cursor>, old: &Rope, new: &Rope) {
+            let remap = |p: Position| {
+                pos_to_char_offset(old, &p)
+                    .and_then(|o| char_offset_to_pos(new, &o))
+                    .unwrap_or_default()
+            };
+            for cursor in cursors.iter_mut() {
+                cursor.position = remap(cursor.position);
+                cursor.sticky_offset = cursor.position.offset;
+                cursor.highlight_position = cursor.highlight_position.map(remap);
+            }
+        }
+
+        remap_all(&mut self.cursors, &old, &new);
+
+        // The cursors in the history are only meaningful for the text as it was when they were
+        // saved, so we walk back, then forward, through the edits to get to each of those texts.
+        let (mut old_counts, mut new_counts) = (self.line_ending_counts, self.line_ending_counts);
+        let (done, undone) = self.history.edits.split_at_mut(self.history.index);
+        for edit in done.iter_mut().rev() {
+            remap_all(&mut edit.cursors_after, &old, &new);
+            for change in edit.changes.iter().rev() {
+                apply_change(&mut old, &mut old_counts, &change.inverse());
+                apply_change(&mut new, &mut new_counts, &change.inverse());
+            }
+            remap_all(&mut edit.cursors_before, &old, &new);
+        }
+
+        let mut old = self.rope.clone();
+        let mut new = self.rope.clone();
+        new.set_line_breaks(line_breaks);
+        let (mut old_counts, mut new_counts) = (self.line_ending_counts, self.line_ending_counts);
+        for edit in undone.iter_mut() {
+            remap_all(&mut edit.cursors_before, &old, &new);
+            for change in edit.changes.iter() {
+                apply_change(&mut old, &mut old_counts, change);
+                apply_change(&mut new, &mut new_counts, change);
+            }
+            remap_all(&mut edit.cursors_after, &old, &new);
+        }
+
+        self.rope.set_line_breaks(line_breaks);
+    }
+
+    fn count_line_endings(&mut self) {
+        let mut counts: LineEndingCounts = d!();
+        for (_, ending) in line_breaks_starting_in(&self.rope, 0, self.rope.len_chars()) {
+            counts.add(ending);
+        }
+        self.line_ending_counts = counts;
+    }
+
+    /// Which kinds of line break the buffer currently has.
+    pub fn line_endings(&self) -> LineEndings {
+        self.line_ending_counts.line_endings()
+    }
+
+    /// Inserts the buffer's preferred kind of line break at each cursor, replacing any
+    /// selections.
+    pub fn insert_line_break(&mut self) {
+        let line_break = self.line_endings().preferred().as_str();
+        self.insert_per_cursor(EditKind::Other, |_| line_break.to_owned());
+    }
+
+    /// Changes every line break in the cursors' selections, or in the whole buffer if nothing is
+    /// selected, to `target`. Returns how many line breaks were changed.
+    pub fn convert_line_endings(&mut self, target: LineEnding) -> usize {
+        self.merge_overlapping_cursors();
+
+        let mut ranges: Vec<_> = self
+            .cursors
+            .iter()
+            .filter_map(|c| selection_range(&self.rope, c))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| (start.0, end.0))
+            .collect();
+        if ranges.is_empty() {
+            ranges.push((0, self.rope.len_chars()));
+        }
+        ranges.sort_by_key(|&(start, _)| start);
+
+        let mut replacements: Vec<Replacement> = Vec::new();
+        for (start, end) in ranges {
+            for (i, ending) in line_breaks_starting_in(&self.rope, start, end) {
+                let len = ending.as_str().len();
+                if ending != target {
+                    replacements.push(Replacement {
+                        start: AbsoluteCharOffset(i),
+                        end: AbsoluteCharOffset(i + len),
+                        text: target.as_str().to_owned(),
+                    });
+                }
+            }
+        }
+
+        if !replacements.is_empty() {
+            self.replace_all(&replacements);
+        }
+        replacements.len()
+    }
+
+    /// Makes all of `replacements`, which must be in order and not overlap, as a single edit,
+    /// keeping the cursors on the same text.
+    fn replace_all(&mut self, replacements: &[Replacement]) {
+        let cursors_before = self.cursors.clone();
+        let offsets: Vec<_> = self
+            .cursors
+            .iter()
+            .map(|c| offset_pair(&self.rope, c))
+            .collect();
+
+        let mut changes = Vec::with_capacity(replacements.len() * 2);
+        // Going backwards means the earlier replacements' offsets are still accurate when we
+        // get to them.
+        for Replacement { start, end, text } in replacements.iter().rev() {
+            if start < end {
+                let change = removal(&self.rope, *start, *end);
+                record_change(
+                    &mut self.rope,
+                    &mut self.line_ending_counts,
+                    &mut changes,
+                    change,
+                );
+            }
+            if !text.is_empty() {
+                let change = Change::Insert {
+                    offset: *start,
+                    text: text.clone(),
+                };
+                record_change(
+                    &mut self.rope,
+                    &mut self.line_ending_counts,
+                    &mut changes,
+                    change,
+                );
+            }
+        }
+
+        let rope = &self.rope;
+        let shifted_pos = |offset: Option<AbsoluteCharOffset>| {
+            offset.and_then(|o| char_offset_to_pos(rope, &shift_offset(replacements, o)))
+        };
+        for (cursor, (position, highlight)) in self.cursors.iter_mut().zip(offsets) {
+            if let Some(p) = shifted_pos(position) {
+                cursor.position = p;
+                cursor.sticky_offset = p.offset;
+            }
+            cursor.highlight_position = shifted_pos(highlight);
+        }
+
+        self.history.record(Edit {
+            kind: EditKind::Other,
+            changes,
+            cursors_before,
+            cursors_after: self.cursors.clone(),
+            version: d!(),
+        });
+    }
+
+    /// The position of the start of the `n`th word in the buffer, counting from 0, if there are
+    /// that many words. This uses the same idea of what a word is as the word moves do.
+    pub fn nth_word_start(&self, mut n: usize) -> Option<Position> {
+        for line in 0..self.rope.len_lines() {
+            let text = line_text(&self.rope, line)?;
+            let starts: Vec<usize> = text
+                .split_word_bound_indices()
+                .filter(|&(_, s)| is_word(s))
+                .map(|(i, _)| i)
+                .collect();
+
+            match starts.get(n) {
+                Some(&start) => {
+                    return Some(Position {
+                        line,
+                        offset: CharOffset(text[..start].chars().count()),
+                    });
+                }
+                None => {
+                    n -= starts.len();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl From<&TextBuffer> for String {
+    fn from(t_b: &TextBuffer) -> String {
+        String::from(&t_b.rope)
+    }
+}
+
+fn byte_range_to_char_range(
+    rope: &Rope,
+    start: usize,
+    end: usize,
+) -> Option<(AbsoluteCharOffset, AbsoluteCharOffset)> {
+    Some((
+        AbsoluteCharOffset(rope.byte_to_char(start)?),
+        AbsoluteCharOffset(rope.byte_to_char(end)?),
+    ))
+}
+
+/// `text` takes the place of the chars in `start..end`.
+struct Replacement {
+    start: AbsoluteCharOffset,
+    end: AbsoluteCharOffset,
+    text: String,
+}
+
+/// Where `offset` ends up after all of `replacements`, which must be in order, are made.
+/// Offsets inside a replaced range move to the start of its replacement.
+fn shift_offset(replacements: &[Replacement], offset: AbsoluteCharOffset) -> AbsoluteCharOffset {
+    let mut added = 0;
+    let mut removed = 0;
+    for Replacement { start, end, text } in replacements {
+        if *end <= offset {
+            added += text.chars().count();
+            removed += end.0 - start.0;
+        } else {
+            if *start < offset {
+                return AbsoluteCharOffset(start.0 + added - removed);
+            }
+            break;
+        }
+    }
+
+    AbsoluteCharOffset(offset.0 + added - removed)
+}
+
+/// Runs the Knuth-Morris-Pratt algorithm over each of the rope's chunks in turn, so matches that
+/// straddle a chunk boundary are found without copying the rope into one contiguous string.
+#[perf_viz::record]
+fn find_all(rope: &Rope, needle: &str) -> Vec<(AbsoluteCharOffset, AbsoluteCharOffset)> {
+    let needle: Vec<char> = needle.chars().collect();
+    let mut output = Vec::new();
+    if needle.is_empty() {
+        return output;
+    }
+
+    // `fallback[i]` is the length of the longest proper prefix of `needle[..=i]` that is also a
+    // suffix of it.
+    let mut fallback = vec![0; needle.len()];
+    let mut matched = 0;
+    for i in 1..needle.len() {
+        while matched > 0 && needle[i] != needle[matched] {
+            matched = fallback[matched - 1];
+        }
+        if needle[i] == needle[matched] {
+            matched += 1;
+        }
+        fallback[i] = matched;
+    }
+
+    let mut matched = 0;
+    let mut offset = 0;
+    for chunk in rope.chunks() {
+        for c in chunk.chars() {
+            offset += 1;
+            while matched > 0 && c != needle[matched] {
+                matched = fallback[matched - 1];
+            }
+            if c == needle[matched] {
+                matched += 1;
+            }
+            if matched == needle.len() {
+                output.push((
+                    AbsoluteCharOffset(offset - needle.len()),
+                    AbsoluteCharOffset(offset),
+                ));
+                matched = 0;
+            }
+        }
+    }
+
+    output
+}
+
 fn backward<P>(rope: &Rope, position: P) -> Position
 where
     P: Borrow<Position>,
@@ -336,11 +1608,28 @@ where
         let line = position.line.saturating_sub(1);
         Position {
             line,
-            offset: nth_line_count(rope, line).unwrap_or_default(),
+            offset: line_text(rope, line)
+                .map(|text| CharOffset(text.chars().count()))
+                .unwrap_or_default(),
         }
     } else {
+        let offset = match line_text(rope, position.line) {
+            Some(text) => {
+                let line_end = CharOffset(text.chars().count());
+                if position.offset > line_end {
+                    line_end
+                } else {
+                    let byte_offset = char_offset_to_byte(&text, position.offset);
+                    let previous =
+                        GraphemeCursor::new(byte_offset, text.len()).prev_boundary(&text, 0);
+                    CharOffset(text[..previous].chars().count())
+                }
+            }
+            None => position.offset - 1,
+        };
+
         Position {
-            offset: position.offset - 1,
+            offset,
             ..*position
         }
     }
@@ -352,18 +1641,22 @@ where
 {
     let position = position.borrow();
 
-    let mut new = Position {
-        offset: position.offset + 1,
-        ..*position
-    };
+    match line_text(rope, position.line) {
+        Some(ref text) if position.offset < text.chars().count() => {
+            let byte_offset = char_offset_to_byte(text, position.offset);
+            let next = GraphemeCursor::new(byte_offset, text.len()).next_boundary(text);
 
-    //  we expect the rest of the system to bounds check on positions
-    if !in_bounds(rope, &new) {
-        new.line += 1;
-        new.offset = d!();
+            Position {
+                offset: CharOffset(text[..next].chars().count()),
+                ..*position
+            }
+        }
+        _ if position.line + 1 < rope.len_lines() => Position {
+            line: position.line + 1,
+            offset: d!(),
+        },
+        _ => *position,
     }
-
-    new
 }
 
 #[cfg(test)]
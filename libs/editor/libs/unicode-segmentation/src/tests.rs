@@ -0,0 +1,116 @@
+use super::*;
+
+/// `s` split at each of its word boundaries.
+fn words(s: &str) -> Vec<&str> {
+    let boundaries: Vec<usize> = (0..=s.len())
+        .filter(|&i| s.is_char_boundary(i) && is_word_boundary(s, i))
+        .collect();
+    boundaries.windows(2).map(|w| &s[w[0]..w[1]]).collect()
+}
+
+#[test]
+fn letters_stay_together_and_everything_else_is_split_off() {
+    assert_eq!(words("Hello, world"), vec!["Hello", ",", " ", "world"]);
+    assert_eq!(words(""), Vec::<&str>::new());
+}
+
+#[test]
+fn the_start_and_end_are_always_boundaries() {
+    assert!(is_word_boundary("ab", 0));
+    assert!(is_word_boundary("ab", 2));
+    assert!(!is_word_boundary("ab", 1));
+}
+
+#[test]
+fn apostrophes_and_other_mid_letters_join_letters_on_both_sides() {
+    // WB6, WB7
+    assert_eq!(words("can't"), vec!["can't"]);
+    assert_eq!(words("e.g"), vec!["e.g"]);
+    assert_eq!(words("a:b"), vec!["a:b"]);
+    // Without a letter on both sides, they are split off.
+    assert_eq!(words("can'"), vec!["can", "'"]);
+    assert_eq!(words("'t"), vec!["'", "t"]);
+    assert_eq!(words("a''b"), vec!["a", "'", "'", "b"]);
+}
+
+#[test]
+fn separators_between_digits_keep_numbers_together() {
+    // WB11, WB12
+    assert_eq!(words("3.14"), vec!["3.14"]);
+    assert_eq!(words("3,000.5"), vec!["3,000.5"]);
+    assert_eq!(words("3;4"), vec!["3;4"]);
+    assert_eq!(words("3."), vec!["3", "."]);
+    assert_eq!(words("3..4"), vec!["3", ".", ".", "4"]);
+    // WB8, WB9, WB10
+    assert_eq!(words("a1b2"), vec!["a1b2"]);
+}
+
+#[test]
+fn underscores_join_letters_numbers_and_other_underscores() {
+    // WB13a, WB13b
+    assert_eq!(words("snake_case_2"), vec!["snake_case_2"]);
+    assert_eq!(words("__init__"), vec!["__init__"]);
+    assert_eq!(words("_ _"), vec!["_", " ", "_"]);
+    assert_eq!(words("a_.b"), vec!["a_", ".", "b"]);
+}
+
+#[test]
+fn crlf_is_one_piece_and_line_breaks_split_from_everything() {
+    // WB3, WB3a, WB3b
+    assert_eq!(words("a\r\nb"), vec!["a", "\r\n", "b"]);
+    assert_eq!(words("\n\r"), vec!["\n", "\r"]);
+    assert_eq!(words("\r\r\n"), vec!["\r", "\r\n"]);
+    assert_eq!(words("a\u{2028}b"), vec!["a", "\u{2028}", "b"]);
+    // Extending chars do not attach to line breaks.
+    assert_eq!(words("\n\u{301}"), vec!["\n", "\u{301}"]);
+}
+
+#[test]
+fn extending_chars_are_part_of_whatever_they_follow() {
+    // WB4
+    assert_eq!(words("e\u{301}te\u{301}"), vec!["e\u{301}te\u{301}"]);
+    assert_eq!(words("a\u{200d}b"), vec!["a\u{200d}b"]);
+    assert_eq!(words("can\u{301}'t"), vec!["can\u{301}'t"]);
+    assert_eq!(words("3\u{ad}.14"), vec!["3\u{ad}.14"]);
+    assert_eq!(words(" \u{301}a"), vec![" \u{301}", "a"]);
+    // With nothing before them, they stand alone.
+    assert_eq!(words("\u{301}a"), vec!["\u{301}", "a"]);
+}
+
+#[test]
+fn zwj_joins_pictographs() {
+    // WB3c
+    let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+    assert_eq!(words(family), vec![family]);
+    assert_eq!(
+        words("\u{1f468} \u{1f469}"),
+        vec!["\u{1f468}", " ", "\u{1f469}"]
+    );
+}
+
+#[test]
+fn spaces_stay_together() {
+    // WB3d
+    assert_eq!(words("a  b"), vec!["a", "  ", "b"]);
+}
+
+#[test]
+fn regional_indicators_pair_up() {
+    // WB15, WB16
+    let us = "\u{1f1fa}\u{1f1f8}";
+    let gb = "\u{1f1ec}\u{1f1e7}";
+    assert_eq!(words(&format!("{}{}", us, gb)), vec![us, gb]);
+    assert_eq!(words(&format!("a{}{}b", us, gb)), vec!["a", us, gb, "b"]);
+}
+
+#[test]
+fn next_and_prev_word_boundary_skip_to_the_neighbouring_boundaries() {
+    let s = "can't stop";
+    assert_eq!(next_word_boundary(s, 0), 5);
+    assert_eq!(next_word_boundary(s, 5), 6);
+    assert_eq!(next_word_boundary(s, 6), s.len());
+    assert_eq!(next_word_boundary(s, s.len()), s.len());
+    assert_eq!(prev_word_boundary(s, s.len()), 6);
+    assert_eq!(prev_word_boundary(s, 3), 0);
+    assert_eq!(prev_word_boundary(s, 0), 0);
+}
@@ -2,6 +2,7 @@
 /// The code is licensed under the Apache 2.0 license, and/or the MIT Licencse as described in the
 /// license files included in this crate. This file has been modified from its original form.
 use grapheme::GraphemeCat;
+use word::WordCat;
 
 pub mod grapheme {
     use std::result::Result::{Err, Ok};
@@ -581,7 +582,130 @@ pub mod grapheme {
         ('\u{e0020}', '\u{e007f}', GC_Extend), ('\u{e0080}', '\u{e00ff}', GC_Control), ('\u{e0100}',
         '\u{e01ef}', GC_Extend), ('\u{e01f0}', '\u{e0fff}', GC_Control)
     ];
+}
+
+pub mod word {
+    pub use self::WordCat::*;
+
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum WordCat {
+        WC_Any,
+        WC_ALetter,
+        WC_CR,
+        WC_Double_Quote,
+        WC_Extend,
+        WC_ExtendNumLet,
+        WC_Format,
+        WC_Hebrew_Letter,
+        WC_Katakana,
+        WC_LF,
+        WC_MidLetter,
+        WC_MidNum,
+        WC_MidNumLet,
+        WC_Newline,
+        WC_Numeric,
+        WC_Regional_Indicator,
+        WC_Single_Quote,
+        WC_WSegSpace,
+        WC_ZWJ,
+    }
 
+    /// Upstream generates a table for this from `WordBreakProperty.txt`. We instead list the
+    /// punctuation and script ranges that the rules care about explicitly and fall back to the
+    /// standard library's alphabetic and numeric classification for everything else.
+    #[perf_viz::record]
+    pub fn word_category(c: char) -> WordCat {
+        use super::grapheme as gr;
+        match c {
+            '\r' => WC_CR,
+            '\n' => WC_LF,
+            '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}' => WC_Newline,
+            '\u{200d}' => WC_ZWJ,
+            '\u{ad}'
+            | '\u{600}'..='\u{605}'
+            | '\u{61c}'
+            | '\u{6dd}'
+            | '\u{70f}'
+            | '\u{8e2}'
+            | '\u{180e}'
+            | '\u{200e}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+            | '\u{110bd}'
+            | '\u{1bca0}'..='\u{1bca3}'
+            | '\u{1d173}'..='\u{1d17a}'
+            | '\u{e0001}' => WC_Format,
+            '\'' => WC_Single_Quote,
+            '"' => WC_Double_Quote,
+            '.' | '\u{2018}' | '\u{2019}' | '\u{2024}' | '\u{fe52}' | '\u{ff07}' | '\u{ff0e}' => {
+                WC_MidNumLet
+            }
+            ':' | '\u{b7}' | '\u{387}' | '\u{55f}' | '\u{5f4}' | '\u{2027}' | '\u{fe13}'
+            | '\u{fe55}' | '\u{ff1a}' => WC_MidLetter,
+            ','
+            | ';'
+            | '\u{37e}'
+            | '\u{589}'
+            | '\u{60c}'..='\u{60d}'
+            | '\u{66c}'
+            | '\u{7f8}'
+            | '\u{2044}'
+            | '\u{fe10}'
+            | '\u{fe14}'
+            | '\u{fe50}'
+            | '\u{fe54}'
+            | '\u{ff0c}'
+            | '\u{ff1b}' => WC_MidNum,
+            '_'
+            | '\u{202f}'
+            | '\u{203f}'..='\u{2040}'
+            | '\u{2054}'
+            | '\u{fe33}'..='\u{fe34}'
+            | '\u{fe4d}'..='\u{fe4f}'
+            | '\u{ff3f}' => WC_ExtendNumLet,
+            ' '
+            | '\u{1680}'
+            | '\u{2000}'..='\u{2006}'
+            | '\u{2008}'..='\u{200a}'
+            | '\u{205f}'
+            | '\u{3000}' => WC_WSegSpace,
+            '\u{3031}'..='\u{3035}'
+            | '\u{309b}'..='\u{309c}'
+            | '\u{30a0}'..='\u{30fa}'
+            | '\u{30fc}'..='\u{30ff}'
+            | '\u{31f0}'..='\u{31ff}'
+            | '\u{32d0}'..='\u{32fe}'
+            | '\u{3300}'..='\u{3357}'
+            | '\u{ff66}'..='\u{ff9d}'
+            | '\u{1b000}' => WC_Katakana,
+            '\u{5d0}'..='\u{5ea}'
+            | '\u{5ef}'..='\u{5f2}'
+            | '\u{fb1d}'
+            | '\u{fb1f}'..='\u{fb28}'
+            | '\u{fb2a}'..='\u{fb4f}' => WC_Hebrew_Letter,
+            // Ideographs, Hiragana and the South East Asian scripts are not `ALetter`, so each
+            // of those characters ends up as a segment of its own.
+            '\u{e00}'..='\u{eff}'
+            | '\u{1000}'..='\u{109f}'
+            | '\u{1780}'..='\u{17ff}'
+            | '\u{3040}'..='\u{309f}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{20000}'..='\u{3134f}' => WC_Any,
+            _ => match gr::grapheme_category(c) {
+                gr::GC_Extend | gr::GC_SpacingMark | gr::GC_E_Modifier => WC_Extend,
+                gr::GC_Regional_Indicator => WC_Regional_Indicator,
+                _ if c.is_numeric() && !c.is_alphabetic() => WC_Numeric,
+                _ if c.is_alphabetic() => WC_ALetter,
+                _ => WC_Any,
+            },
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -927,10 +1051,232 @@ impl GraphemeCursor {
     }
 }
 
+#[derive(Clone)]
+pub struct WordBoundIndices<'a> {
+    string: &'a str,
+    offset: usize,
+}
+
+impl<'a> Iterator for WordBoundIndices<'a> {
+    type Item = (usize, &'a str);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let start = self.offset;
+        if start >= self.string.len() {
+            return None;
+        }
+        self.offset = next_word_boundary(self.string, start);
+        Some((start, &self.string[start..self.offset]))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let slen = self.string.len() - self.offset;
+        (std::cmp::min(slen, 1), Some(slen))
+    }
+}
+
+/// External iterator for a string's
+/// [word boundaries](http://www.unicode.org/reports/tr29/#Word_Boundaries). Every byte of the
+/// string is in exactly one of the yielded segments, including whitespace and punctuation.
+#[derive(Clone)]
+pub struct WordBounds<'a> {
+    iter: WordBoundIndices<'a>,
+}
+
+impl<'a> Iterator for WordBounds<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        self.iter.next().map(|(_, s)| s)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[inline]
+pub fn new_word_bounds<'b>(s: &'b str) -> WordBounds<'b> {
+    WordBounds {
+        iter: new_word_bound_indices(s),
+    }
+}
+
+#[inline]
+pub fn new_word_bound_indices<'b>(s: &'b str) -> WordBoundIndices<'b> {
+    WordBoundIndices {
+        string: s,
+        offset: 0,
+    }
+}
+
+fn is_ignorable(cat: WordCat) -> bool {
+    use word::*;
+    cat == WC_Extend || cat == WC_Format || cat == WC_ZWJ
+}
+
+fn is_line_break(cat: WordCat) -> bool {
+    use word::*;
+    cat == WC_CR || cat == WC_LF || cat == WC_Newline
+}
+
+fn is_ah_letter(cat: WordCat) -> bool {
+    use word::*;
+    cat == WC_ALetter || cat == WC_Hebrew_Letter
+}
+
+fn is_mid_num_let_q(cat: WordCat) -> bool {
+    use word::*;
+    cat == WC_MidNumLet || cat == WC_Single_Quote
+}
+
+fn is_pictographic(c: char) -> bool {
+    use grapheme as gr;
+    match gr::grapheme_category(c) {
+        gr::GC_Glue_After_Zwj | gr::GC_E_Base | gr::GC_E_Base_GAZ => true,
+        _ => false,
+    }
+}
+
+/// Returns whether there is a word boundary at the byte offset `offset` in `s`. The start and
+/// end of the string are always boundaries. `offset` must be on a `char` boundary.
+#[perf_viz::record]
+pub fn is_word_boundary(s: &str, offset: usize) -> bool {
+    use word::*;
+    // WB1, WB2
+    if offset == 0 || offset >= s.len() {
+        return true;
+    }
+    let (before, after) = s.split_at(offset);
+    let first_after = after.chars().next().unwrap();
+    let cat_before = word_category(before.chars().rev().next().unwrap());
+    let cat_after = word_category(first_after);
+
+    match (cat_before, cat_after) {
+        (WC_CR, WC_LF) => return false,                                // WB3
+        (WC_CR, _) | (WC_LF, _) | (WC_Newline, _) => return true,      // WB3a
+        (_, WC_CR) | (_, WC_LF) | (_, WC_Newline) => return true,      // WB3b
+        (WC_ZWJ, _) if is_pictographic(first_after) => return false,   // WB3c
+        (WC_WSegSpace, WC_WSegSpace) => return false,                  // WB3d
+        (_, WC_Extend) | (_, WC_Format) | (_, WC_ZWJ) => return false, // WB4
+        _ => {}
+    }
+
+    // WB4 says to treat `X (Extend | Format | ZWJ)*` as `X`, so the rest of the rules skip over
+    // those categories when looking for context.
+    let significant = |c: char| {
+        let cat = word_category(c);
+        if is_ignorable(cat) {
+            None
+        } else {
+            Some(cat)
+        }
+    };
+    let mut left = before.chars().rev().filter_map(significant);
+    let mut right = after.chars().filter_map(significant).skip(1);
+
+    let l1 = match left.next() {
+        Some(cat) if !is_line_break(cat) => cat,
+        // The ignorable characters did not attach to anything, so they stand alone.
+        _ => return true,
+    };
+    let r1 = cat_after;
+    let l2 = left.next();
+    let r2 = right.next();
+
+    let ah_letter_or_numeric = |cat: WordCat| is_ah_letter(cat) || cat == WC_Numeric;
+
+    if is_ah_letter(l1) && is_ah_letter(r1) {
+        // WB5
+        false
+    } else if is_ah_letter(l1)
+        && (r1 == WC_MidLetter || is_mid_num_let_q(r1))
+        && r2.map_or(false, is_ah_letter)
+    {
+        // WB6
+        false
+    } else if (l1 == WC_MidLetter || is_mid_num_let_q(l1))
+        && is_ah_letter(r1)
+        && l2.map_or(false, is_ah_letter)
+    {
+        // WB7
+        false
+    } else if l1 == WC_Hebrew_Letter && r1 == WC_Single_Quote {
+        // WB7a
+        false
+    } else if l1 == WC_Hebrew_Letter && r1 == WC_Double_Quote && r2 == Some(WC_Hebrew_Letter) {
+        // WB7b
+        false
+    } else if l1 == WC_Double_Quote && r1 == WC_Hebrew_Letter && l2 == Some(WC_Hebrew_Letter) {
+        // WB7c
+        false
+    } else if ah_letter_or_numeric(l1) && r1 == WC_Numeric || l1 == WC_Numeric && is_ah_letter(r1) {
+        // WB8, WB9, WB10
+        false
+    } else if (r1 == WC_MidNum || is_mid_num_let_q(r1))
+        && l1 == WC_Numeric
+        && r2 == Some(WC_Numeric)
+        || (l1 == WC_MidNum || is_mid_num_let_q(l1)) && r1 == WC_Numeric && l2 == Some(WC_Numeric)
+    {
+        // WB11, WB12
+        false
+    } else if l1 == WC_Katakana && r1 == WC_Katakana {
+        // WB13
+        false
+    } else if (ah_letter_or_numeric(l1) || l1 == WC_Katakana || l1 == WC_ExtendNumLet)
+        && r1 == WC_ExtendNumLet
+        || l1 == WC_ExtendNumLet && (ah_letter_or_numeric(r1) || r1 == WC_Katakana)
+    {
+        // WB13a, WB13b
+        false
+    } else if l1 == WC_Regional_Indicator && r1 == WC_Regional_Indicator {
+        // WB15, WB16
+        let ris_count = before
+            .chars()
+            .rev()
+            .filter_map(significant)
+            .take_while(|&cat| cat == WC_Regional_Indicator)
+            .count();
+        ris_count % 2 == 0
+    } else {
+        // WB999
+        true
+    }
+}
+
+/// Returns the byte offset of the first word boundary after `offset`, or the length of `s` if
+/// there is none.
+pub fn next_word_boundary(s: &str, offset: usize) -> usize {
+    s[offset..]
+        .char_indices()
+        .skip(1)
+        .map(|(i, _)| offset + i)
+        .find(|&i| is_word_boundary(s, i))
+        .unwrap_or_else(|| s.len())
+}
+
+/// Returns the byte offset of the last word boundary before `offset`, or 0 if there is none.
+pub fn prev_word_boundary(s: &str, offset: usize) -> usize {
+    s[..offset]
+        .char_indices()
+        .rev()
+        .map(|(i, _)| i)
+        .find(|&i| is_word_boundary(s, i))
+        .unwrap_or(0)
+}
+
 pub trait UnicodeSegmentation {
     fn graphemes<'a>(&'a self) -> Graphemes<'a>;
 
     fn grapheme_indices<'a>(&'a self) -> GraphemeIndices<'a>;
+
+    fn split_word_bounds<'a>(&'a self) -> WordBounds<'a>;
+
+    fn split_word_bound_indices<'a>(&'a self) -> WordBoundIndices<'a>;
 }
 
 impl UnicodeSegmentation for str {
@@ -943,4 +1289,17 @@ impl UnicodeSegmentation for str {
     fn grapheme_indices(&self) -> GraphemeIndices {
         new_grapheme_indices(self)
     }
+
+    #[inline]
+    fn split_word_bounds(&self) -> WordBounds {
+        new_word_bounds(self)
+    }
+
+    #[inline]
+    fn split_word_bound_indices(&self) -> WordBoundIndices {
+        new_word_bound_indices(self)
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -1,14 +1,116 @@
-use editor_types::{Cursor, MultiCursorBuffer, Vec1};
-use macros::{d, dg};
+use editor_types::{ByteIndex, Cursor, MultiCursorBuffer, Vec1};
+use macros::d;
 use platform_types::{
-    position_to_screen_space, screen_space_to_position, BufferView, CharDim, Cmd, Highlight, Input,
-    ScreenSpaceXY, UpdateAndRenderOutput, View,
+    position_to_screen_space, screen_space_to_position, AbsoluteCharOffset, BufferId, BufferView,
+    CharDim, CharOffset, Cmd, Highlight, Input, Move, Position, ScreenSpaceXY,
+    UpdateAndRenderOutput, View,
 };
-use text_buffer::TextBuffer;
+use std::ops::Range;
+use std::path::PathBuf;
+use text_buffer::{LineBreaks, LineEnding, TextBuffer, Version};
+
+#[derive(Default)]
+struct EditorBuffer {
+    text_buffer: TextBuffer,
+    /// `None` means the buffer has never been saved.
+    path: Option<PathBuf>,
+    id: BufferId,
+    /// The version of the text that is in the file at `path`, or, if there is no `path`, the
+    /// empty text the buffer started with.
+    saved_version: Version,
+    /// The version of the text in the most recent `Cmd::SaveFile` or `Cmd::SaveFileAs`, which
+    /// becomes the `saved_version` once the platform layer says the save worked.
+    saving_version: Version,
+}
+
+impl EditorBuffer {
+    fn has_unsaved_changes(&self) -> bool {
+        self.text_buffer.version() != self.saved_version
+    }
+}
+
+impl From<TextBuffer> for EditorBuffer {
+    fn from(text_buffer: TextBuffer) -> Self {
+        EditorBuffer {
+            text_buffer,
+            path: None,
+            id: d!(),
+            saved_version: d!(),
+            saving_version: d!(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum PromptKind {
+    Open,
+    SaveAs,
+    GoTo,
+    Find,
+    ReplacePattern,
+    /// The regex to replace, from the `ReplacePattern` prompt.
+    ReplaceWith(String),
+    ConvertLineEndings,
+    LineBreaks,
+    /// Asks before closing the buffer with this id, since it has unsaved changes.
+    CloseUnsaved(BufferId),
+}
+
+/// A single line of text the user is typing into the status line. Submitting an empty prompt
+/// cancels it, except for `ReplaceWith`, since replacing with nothing is reasonable. Escape, which
+/// sends `Input::CollapseCursors`, cancels any prompt.
+#[derive(Debug)]
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Prompt { kind, text: d!() }
+    }
+
+    fn label(&self) -> &'static str {
+        match self.kind {
+            PromptKind::Open => "Open: ",
+            PromptKind::SaveAs => "Save as: ",
+            PromptKind::GoTo => "Go to (word, line:col, cN, bN or N%): ",
+            PromptKind::Find => "Find: ",
+            PromptKind::ReplacePattern => "Replace regex: ",
+            PromptKind::ReplaceWith(_) => "Replace with ($1 for a capture group): ",
+            PromptKind::ConvertLineEndings => "Convert line endings to (LF, CRLF or CR): ",
+            PromptKind::LineBreaks => "Split lines on (LF, CR or LF, or Unicode): ",
+            PromptKind::CloseUnsaved(_) => "Unsaved changes. Close anyway? (yes or no): ",
+        }
+    }
+
+    fn highlights_matches(&self) -> bool {
+        match self.kind {
+            PromptKind::Find | PromptKind::ReplacePattern => true,
+            _ => false,
+        }
+    }
+}
+
+/// Controls how the view follows the primary cursor around.
+#[derive(Clone, Copy, Debug)]
+pub struct ScrollSettings {
+    /// How many columns and lines to keep between the primary cursor and the edges of the
+    /// screen. This is reduced automatically when the screen is too small to fit it.
+    pub margin: usize,
+    /// Keep the primary cursor in the middle of the screen horizontally, and scroll the text
+    /// under it instead.
+    pub center_horizontally: bool,
+}
+
+d!(for ScrollSettings: ScrollSettings {
+    margin: 2,
+    center_horizontally: false,
+});
 
 #[derive(Default)]
 pub struct State {
-    buffers: Vec1<TextBuffer>,
+    buffers: Vec1<EditorBuffer>,
     current_burrer_index: usize,
     scroll_x: f32,
     scroll_y: f32,
@@ -18,21 +120,95 @@ pub struct State {
     mouse_y: f32,
     text_char_dim: CharDim,
     status_char_dim: CharDim,
+    prompt: Option<Prompt>,
+    status_message: Option<String>,
+    scroll_settings: ScrollSettings,
+    /// The most recent thing searched for, kept so `FindNext` and `FindPrevious` still work after
+    /// the find prompt is closed.
+    search_query: String,
+    /// Where the primary cursor was when the find prompt was opened. As the query changes, we
+    /// jump to the first match after this, and if the prompt is cancelled, we go back here.
+    search_origin: Position,
+    /// The matches for `search_query`, which are highlighted while the find prompt is open.
+    search_matches: Vec<(Position, Position)>,
+    /// Why the regex in the replace prompt is invalid, if it is.
+    search_error: Option<String>,
+    /// The id given to the most recently made buffer. The first buffer has the default id.
+    last_buffer_id: BufferId,
+    /// Set when the cursor was placed before we knew how big the screen is, so we can scroll to
+    /// it once we do.
+    scroll_to_cursor_once_sized: bool,
 }
 
 impl State {
     #[perf_viz::record]
     fn current_buffer(&self) -> Option<&TextBuffer> {
-        self.buffers.get(self.current_burrer_index)
+        self.buffers
+            .get(self.current_burrer_index)
+            .map(|b| &b.text_buffer)
     }
     #[perf_viz::record]
     fn current_buffer_mut(&mut self) -> Option<&mut TextBuffer> {
-        self.buffers.get_mut(self.current_burrer_index)
+        self.buffers
+            .get_mut(self.current_burrer_index)
+            .map(|b| &mut b.text_buffer)
+    }
+
+    fn next_buffer_id(&mut self) -> BufferId {
+        self.last_buffer_id = BufferId(self.last_buffer_id.0 + 1);
+        self.last_buffer_id
+    }
+
+    fn buffer_with_id_mut(&mut self, id: BufferId) -> Option<&mut EditorBuffer> {
+        self.buffers.iter_mut().find(|b| b.id == id)
+    }
+
+    /// Starts saving the current buffer to `path`, and returns the `Cmd` that does it.
+    fn save_current_buffer(&mut self, path: PathBuf, save_as: bool) -> Cmd {
+        match self.buffers.get_mut(self.current_burrer_index) {
+            Some(b) => {
+                b.saving_version = b.text_buffer.version();
+                let contents = (&b.text_buffer).into();
+                if save_as {
+                    Cmd::SaveFileAs(b.id, path, contents)
+                } else {
+                    Cmd::SaveFile(b.id, path, contents)
+                }
+            }
+            None => Cmd::NoCmd,
+        }
     }
 
     pub fn new() -> State {
         d!()
     }
+
+    pub fn set_scroll_settings(&mut self, scroll_settings: ScrollSettings) {
+        self.scroll_settings = scroll_settings;
+    }
+
+    /// Opens `contents` as the buffer for `path`, the same way a `Input::LoadedFile` would, then
+    /// places a single cursor as close to `position` as the contents allow.
+    pub fn open_at(&mut self, path: PathBuf, contents: String, position: Position) {
+        load_file(self, path, contents);
+
+        if let Some(b) = self.current_buffer_mut() {
+            if b.in_bounds(position) {
+                *b.cursors_mut() = Vec1::new(Cursor::new(position));
+            } else if let Some(p) = b.nearest_valid_position_on_same_line(position) {
+                *b.cursors_mut() = Vec1::new(Cursor::new(p));
+            } else {
+                *b.cursors_mut() = d!();
+                b.move_all_cursors(Move::ToBufferEnd);
+            }
+        }
+
+        if is_sized(self) {
+            scroll_to_primary_cursor(self);
+        } else {
+            self.scroll_to_cursor_once_sized = true;
+        }
+    }
 }
 
 pub fn new() -> State {
@@ -43,7 +219,7 @@ impl From<String> for State {
     fn from(s: String) -> Self {
         let mut output: Self = d!();
 
-        output.buffers = Vec1::new(TextBuffer::from(s));
+        output.buffers = Vec1::new(TextBuffer::from(s).into());
 
         output
     }
@@ -53,7 +229,7 @@ impl From<&str> for State {
     fn from(s: &str) -> Self {
         let mut output: Self = d!();
 
-        output.buffers = Vec1::new(TextBuffer::from(s));
+        output.buffers = Vec1::new(TextBuffer::from(s).into());
 
         output
     }
@@ -97,12 +273,28 @@ pub fn render_view(state: &State, view: &mut View) {
                 }
             }
 
+            if state
+                .prompt
+                .as_ref()
+                .map_or(false, Prompt::highlights_matches)
+            {
+                highlights.extend(state.search_matches.iter().cloned().map(Highlight::new));
+            }
+
+            let (lines, columns) = visible_window(state);
+            let chars_start = Position {
+                line: lines.start,
+                offset: CharOffset(columns.start),
+            };
+            let (chars, line_ends) = buffer.text_window(lines, columns);
             view.buffers.push(BufferView {
                 kind: BufferViewKind::Edit,
                 screen_position: (state.scroll_x, state.scroll_y),
                 bounds: (std::f32::INFINITY, std::f32::INFINITY),
                 color: [0.3, 0.3, 0.9, 1.0],
-                chars: buffer.chars().collect::<String>(),
+                chars,
+                chars_start,
+                line_ends,
                 highlights,
             });
 
@@ -111,31 +303,59 @@ pub fn render_view(state: &State, view: &mut View) {
                 screen_position: (0.0, status_line_y),
                 bounds: (state.screen_w, state.text_char_dim.h),
                 color: [0.3, 0.9, 0.3, 1.0],
-                chars: {
+                chars: if let Some(prompt) = &state.prompt {
+                    match prompt.kind {
+                        PromptKind::ReplacePattern if state.search_error.is_some() => format!(
+                            "{}{} ({})",
+                            prompt.label(),
+                            prompt.text,
+                            state.search_error.as_ref().map_or("", |e| e.as_str())
+                        ),
+                        PromptKind::Find | PromptKind::ReplacePattern
+                            if !prompt.text.is_empty() =>
+                        {
+                            format!(
+                                "{}{} ({} matches)",
+                                prompt.label(),
+                                prompt.text,
+                                state.search_matches.len()
+                            )
+                        }
+                        _ => format!("{}{}", prompt.label(), prompt.text),
+                    }
+                } else {
                     use std::fmt::Write;
                     let mut chars = String::with_capacity(state.screen_w as usize);
 
+                    for (i, b) in state.buffers.iter().enumerate() {
+                        let _cannot_actually_fail = if i == state.current_burrer_index {
+                            match &b.path {
+                                Some(path) => write!(chars, "[{}] ", path.display()),
+                                None => write!(chars, "[*scratch {}*] ", i + 1),
+                            }
+                        } else {
+                            match b.path.as_ref().and_then(|p| p.file_name()) {
+                                Some(name) => write!(chars, "{} ", name.to_string_lossy()),
+                                None => write!(chars, "*scratch {}* ", i + 1),
+                            }
+                        };
+                    }
+
                     let _cannot_actually_fail = write!(
                         chars,
-                        "m{:?} c{:?} ",
-                        (state.mouse_x, state.mouse_y),
-                        (state.text_char_dim.w, state.text_char_dim.h)
+                        "{} ({} lines) ",
+                        buffer.line_endings(),
+                        buffer.line_breaks()
                     );
 
-                    chars = buffer.cursors().iter().fold(chars, |mut acc, c| {
-                        let _cannot_actually_fail = write!(
-                            acc,
-                            "{} ({:?}|{:?})",
-                            c,
-                            buffer.find_index(c).and_then(|o| if o == 0 {
-                                None
-                            } else {
-                                Some(o - 1)
-                            }),
-                            buffer.find_index(c),
-                        );
-                        acc
-                    });
+                    if let Some(message) = &state.status_message {
+                        let _cannot_actually_fail = write!(chars, "{} ", message);
+                    }
+
+                    for (i, c) in buffer.cursors().iter().enumerate() {
+                        let separator = if i == 0 { "" } else { " " };
+                        let _cannot_actually_fail = write!(chars, "{}{}", separator, c);
+                    }
 
                     chars
                 },
@@ -155,6 +375,23 @@ pub fn render_view(state: &State, view: &mut View) {
     };
 }
 
+/// The lines and columns of the buffer which are at least partly on screen.
+fn visible_window(state: &State) -> (Range<usize>, Range<usize>) {
+    let CharDim { w, h } = state.text_char_dim;
+    // Before the sizes are set these divisions can give infinity or NaN. `as usize` saturates
+    // those to `usize::MAX` and `0`, which at worst means drawing more than fits on screen.
+    let first_line = (-state.scroll_y / h).max(0.0) as usize;
+    let first_column = (-state.scroll_x / w).max(0.0) as usize;
+    // One extra, for the line and column cut off at the top and left.
+    let line_count = ((state.screen_h / h).ceil() as usize).saturating_add(1);
+    let column_count = ((state.screen_w / w).ceil() as usize).saturating_add(1);
+
+    (
+        first_line..first_line.saturating_add(line_count),
+        first_column..first_column.saturating_add(column_count),
+    )
+}
+
 macro_rules! set_if_present {
     ($source:ident => $target:ident.$field:ident) => {
         if let Some($field) = $source.$field {
@@ -165,26 +402,92 @@ macro_rules! set_if_present {
 
 #[perf_viz::record]
 pub fn update_and_render(state: &mut State, input: Input) -> UpdateAndRenderOutput {
-    if cfg!(debug_assertions) {
-        if let Input::SetMousePos(_) = input {
+    let mut cmd = Cmd::NoCmd;
+
+    let prompt_text_before = state.prompt.as_ref().map(|p| p.text.clone());
+
+    let should_follow_cursor = match input {
+        Input::Insert(_)
+        | Input::InsertLineBreak
+        | Input::Delete
+        | Input::DeleteTo(_)
+        | Input::MoveAllCursors(_)
+        | Input::ExtendSelectionForAllCursors(_)
+        | Input::ReplaceCursors(_)
+        | Input::AddCursor(_)
+        | Input::SelectWord(_)
+        | Input::SelectLine(_)
+        | Input::AddCursorAbove
+        | Input::AddCursorBelow
+        | Input::CollapseCursors
+        | Input::LoadedFile(..)
+        | Input::NextBuffer
+        | Input::PreviousBuffer
+        | Input::SelectBuffer(_)
+        | Input::NewScratchBuffer
+        | Input::CloseBuffer
+        | Input::Undo
+        | Input::Redo
+        | Input::Cut
+        | Input::Paste(_)
+        | Input::FindNext
+        | Input::FindPrevious
+        | Input::AddCursorAtNextOccurrence
+        | Input::SelectAllOccurrences => true,
+        _ => false,
+    };
 
-        } else {
-            dg!(input);
-        }
-    }
     match input {
         Input::None => {}
         Input::Quit => {}
-        Input::Insert(c) => {
-            if let Some(b) = state.current_buffer_mut() {
-                b.insert(c);
+        Input::Insert(c) => match state.prompt {
+            Some(ref mut prompt) if c != '\n' => {
+                prompt.text.push(c);
             }
-        }
-        Input::Delete => {
-            if let Some(b) = state.current_buffer_mut() {
-                b.delete();
+            Some(_) => {
+                cmd = submit_prompt(state);
             }
-        }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.insert(c);
+                }
+            }
+        },
+        Input::InsertLineBreak => match state.prompt {
+            Some(_) => {
+                cmd = submit_prompt(state);
+            }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.insert_line_break();
+                }
+            }
+        },
+        Input::Delete => match state.prompt {
+            Some(ref mut prompt) => {
+                prompt.text.pop();
+            }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.delete();
+                }
+            }
+        },
+        Input::DeleteTo(r#move) => match state.prompt {
+            Some(ref mut prompt) => {
+                // The prompt's cursor is always at the end of its text, so this deletes what
+                // the same move would from the end of a buffer holding that text.
+                let mut text_buffer = TextBuffer::from(prompt.text.as_str());
+                text_buffer.move_all_cursors(Move::ToBufferEnd);
+                text_buffer.delete_to(r#move);
+                prompt.text = String::from(&text_buffer);
+            }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.delete_to(r#move);
+                }
+            }
+        },
         Input::MoveAllCursors(r#move) => {
             if let Some(b) = state.current_buffer_mut() {
                 b.move_all_cursors(r#move);
@@ -210,6 +513,11 @@ pub fn update_and_render(state: &mut State, input: Input) -> UpdateAndRenderOutp
             set_if_present!(sizes => state.screen_h);
             set_if_present!(sizes => state.text_char_dim);
             set_if_present!(sizes => state.status_char_dim);
+
+            if state.scroll_to_cursor_once_sized && is_sized(state) {
+                state.scroll_to_cursor_once_sized = false;
+                scroll_to_primary_cursor(state);
+            }
         }
         Input::SetMousePos(ScreenSpaceXY { x, y }) => {
             state.mouse_x = x;
@@ -219,20 +527,660 @@ pub fn update_and_render(state: &mut State, input: Input) -> UpdateAndRenderOutp
             let position =
                 screen_space_to_position(xy, state.text_char_dim, (state.scroll_x, state.scroll_y));
             if let Some(b) = state.current_buffer_mut() {
-                if b.in_bounds(position) {
-                    let cursors = b.cursors_mut();
-                    *cursors = Vec1::new(Cursor::new(position));
-                } else if let Some(p) = b.nearest_valid_position_on_same_line(position) {
+                if let Some(p) = valid_position(b, position) {
                     let cursors = b.cursors_mut();
                     *cursors = Vec1::new(Cursor::new(p));
                 }
             }
         }
+        Input::AddCursor(xy) => {
+            let position =
+                screen_space_to_position(xy, state.text_char_dim, (state.scroll_x, state.scroll_y));
+            if let Some(b) = state.current_buffer_mut() {
+                if let Some(p) = valid_position(b, position) {
+                    b.add_cursor(p);
+                }
+            }
+        }
+        Input::DragCursors(xy) => {
+            let position =
+                screen_space_to_position(xy, state.text_char_dim, (state.scroll_x, state.scroll_y));
+            if let Some(b) = state.current_buffer_mut() {
+                if let Some(p) = valid_position(b, position) {
+                    b.drag_cursor_to(p);
+                }
+            }
+        }
+        Input::SelectWord(xy) => {
+            let position =
+                screen_space_to_position(xy, state.text_char_dim, (state.scroll_x, state.scroll_y));
+            if let Some(b) = state.current_buffer_mut() {
+                if let Some(p) = valid_position(b, position) {
+                    b.select_word_at(p);
+                }
+            }
+        }
+        Input::SelectLine(xy) => {
+            let position =
+                screen_space_to_position(xy, state.text_char_dim, (state.scroll_x, state.scroll_y));
+            if let Some(b) = state.current_buffer_mut() {
+                if let Some(p) = valid_position(b, position) {
+                    b.select_line_at(p);
+                }
+            }
+        }
+        Input::AddCursorAbove => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.add_cursor_on_adjacent_line(true);
+            }
+        }
+        Input::AddCursorBelow => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.add_cursor_on_adjacent_line(false);
+            }
+        }
+        Input::CollapseCursors => match state.prompt.take() {
+            Some(prompt) => {
+                state.search_matches.clear();
+                state.search_error = None;
+                // Cancelling a find goes back to where it started, instead of the last match.
+                if let PromptKind::Find = prompt.kind {
+                    let origin = state.search_origin;
+                    if let Some(b) = state.current_buffer_mut() {
+                        *b.cursors_mut() = Vec1::new(Cursor::new(origin));
+                    }
+                }
+            }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.collapse_cursors();
+                }
+            }
+        },
+        Input::Open => {
+            state.prompt = Some(Prompt::new(PromptKind::Open));
+        }
+        Input::Save => match state.buffers.get(state.current_burrer_index) {
+            Some(EditorBuffer {
+                path: Some(path), ..
+            }) => {
+                let path = path.clone();
+                cmd = state.save_current_buffer(path, false);
+            }
+            Some(_) => {
+                state.prompt = Some(Prompt::new(PromptKind::SaveAs));
+            }
+            None => {}
+        },
+        Input::SaveAs => {
+            state.prompt = Some(Prompt::new(PromptKind::SaveAs));
+        }
+        Input::LoadedFile(path, contents) => {
+            load_file(state, path, contents);
+        }
+        Input::Saved(id, path) => {
+            state.status_message = Some(format!("Saved {}", path.display()));
+            // If the buffer was closed while it was being saved, there is nothing to update.
+            if let Some(b) = state.buffer_with_id_mut(id) {
+                b.saved_version = b.saving_version;
+            }
+        }
+        Input::SavedAs(id, path) => {
+            state.status_message = Some(format!("Saved {}", path.display()));
+            if let Some(b) = state.buffer_with_id_mut(id) {
+                b.saved_version = b.saving_version;
+                b.path = Some(path);
+            }
+        }
+        Input::FileError(id, path, error) => {
+            state.status_message = Some(format!("{}: {}", path.display(), error));
+            // The file still has whatever it had before the failed save.
+            if let Some(b) = id.and_then(|id| state.buffer_with_id_mut(id)) {
+                b.saving_version = b.saved_version;
+            }
+        }
+        Input::NextBuffer => {
+            state.current_burrer_index = (state.current_burrer_index + 1) % state.buffers.len();
+        }
+        Input::PreviousBuffer => {
+            let len = state.buffers.len();
+            state.current_burrer_index = (state.current_burrer_index + len - 1) % len;
+        }
+        Input::SelectBuffer(index) => {
+            if index < state.buffers.len() {
+                state.current_burrer_index = index;
+            }
+        }
+        Input::NewScratchBuffer => {
+            let id = state.next_buffer_id();
+            state.buffers.push(EditorBuffer { id, ..d!() });
+            state.current_burrer_index = state.buffers.len() - 1;
+        }
+        Input::CloseBuffer => match state.buffers.get(state.current_burrer_index) {
+            Some(b) if b.has_unsaved_changes() => {
+                state.prompt = Some(Prompt::new(PromptKind::CloseUnsaved(b.id)));
+            }
+            _ => {
+                close_buffer(state, state.current_burrer_index);
+            }
+        },
+        Input::Undo => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.undo();
+            }
+        }
+        Input::Redo => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.redo();
+            }
+        }
+        Input::Cut => {
+            if let Some(b) = state.current_buffer_mut() {
+                cmd = set_clipboard_cmd(b.cut_selections());
+            }
+        }
+        Input::Copy => {
+            if let Some(b) = state.current_buffer() {
+                cmd = set_clipboard_cmd(b.copy_selections());
+            }
+        }
+        Input::Paste(s) => match state.prompt {
+            Some(ref mut prompt) => {
+                prompt.text.push_str(&s);
+            }
+            None => {
+                if let Some(b) = state.current_buffer_mut() {
+                    b.insert_string(s);
+                }
+            }
+        },
+        Input::GoTo => {
+            state.prompt = Some(Prompt::new(PromptKind::GoTo));
+        }
+        Input::Find => {
+            if let Some(b) = state.current_buffer() {
+                state.search_origin = b.cursors().first().position;
+            }
+            state.search_matches.clear();
+            state.prompt = Some(Prompt::new(PromptKind::Find));
+        }
+        Input::FindNext => {
+            find_next(state, true);
+        }
+        Input::FindPrevious => {
+            find_next(state, false);
+        }
+        Input::AddCursorAtNextOccurrence => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.add_cursor_at_next_occurrence();
+            }
+        }
+        Input::SelectAllOccurrences => {
+            if let Some(b) = state.current_buffer_mut() {
+                b.select_all_occurrences();
+            }
+        }
+        Input::ConvertLineEndings => {
+            state.prompt = Some(Prompt::new(PromptKind::ConvertLineEndings));
+        }
+        Input::SetLineBreaks => {
+            state.prompt = Some(Prompt::new(PromptKind::LineBreaks));
+        }
+        Input::Replace => {
+            state.search_matches.clear();
+            state.search_error = None;
+            state.prompt = Some(Prompt::new(PromptKind::ReplacePattern));
+        }
+    }
+
+    // Most inputs, like the mouse moving, leave the prompt alone, and there is no point in
+    // searching again for the same thing.
+    let prompt_text_changed = state.prompt.as_ref().map(|p| &p.text) != prompt_text_before.as_ref();
+
+    // Searching is incremental, so every change to the query moves the cursor. An empty query
+    // leaves the previous one in place for `FindNext` and `FindPrevious`.
+    if let Some(Prompt {
+        kind: PromptKind::Find,
+        text,
+    }) = &state.prompt
+    {
+        if text.is_empty() {
+            state.search_matches.clear();
+        } else if prompt_text_changed {
+            state.search_query = text.clone();
+            search_from_origin(state);
+        }
+    }
+    if let Some(Prompt {
+        kind: PromptKind::ReplacePattern,
+        text,
+    }) = &state.prompt
+    {
+        if prompt_text_changed {
+            let found = if text.is_empty() {
+                Ok(Vec::new())
+            } else {
+                state
+                    .current_buffer()
+                    .map_or(Ok(Vec::new()), |b| b.regex_find_all(text))
+            };
+            match found {
+                Ok(matches) => {
+                    state.search_matches = matches;
+                    state.search_error = None;
+                }
+                Err(e) => {
+                    state.search_matches.clear();
+                    state.search_error = Some(regex_error_summary(&e));
+                }
+            }
+        }
+    }
+
+    if should_follow_cursor {
+        scroll_to_primary_cursor(state);
     }
 
     let mut view = d!();
 
     render_view(state, &mut view);
 
-    (view, Cmd::NoCmd)
+    (view, cmd)
 }
+
+/// The given position if it is in the buffer, otherwise the nearest one on the same line, or the
+/// end of the buffer if the position is past the last line.
+fn valid_position(b: &TextBuffer, position: Position) -> Option<Position> {
+    if b.in_bounds(position) {
+        Some(position)
+    } else {
+        b.nearest_valid_position_on_same_line(position)
+            .or_else(|| b.char_offset_to_pos(AbsoluteCharOffset(b.len_chars())))
+    }
+}
+
+/// The selections go on separate lines, so that pasting with the same number of cursors puts
+/// each one back where it came from.
+fn set_clipboard_cmd(selections: Vec<String>) -> Cmd {
+    if selections.iter().all(String::is_empty) {
+        Cmd::NoCmd
+    } else {
+        Cmd::SetClipboard(selections.join("\n"))
+    }
+}
+
+fn submit_prompt(state: &mut State) -> Cmd {
+    match state.prompt.take() {
+        // Replacing with nothing is a reasonable thing to want, so this comes before the check
+        // for an empty prompt.
+        Some(Prompt {
+            kind: PromptKind::ReplaceWith(pattern),
+            text,
+        }) => {
+            let replaced = state
+                .current_buffer_mut()
+                .map(|b| b.regex_replace_all(&pattern, &text));
+            state.status_message = match replaced {
+                Some(Ok(count)) => Some(format!("Replaced {} matches", count)),
+                Some(Err(e)) => Some(regex_error_summary(&e)),
+                None => None,
+            };
+            Cmd::NoCmd
+        }
+        Some(Prompt { ref text, .. }) if text.is_empty() => Cmd::NoCmd,
+        Some(Prompt {
+            kind: PromptKind::Open,
+            text,
+        }) => Cmd::LoadFile(PathBuf::from(text)),
+        Some(Prompt {
+            kind: PromptKind::SaveAs,
+            text,
+        }) => state.save_current_buffer(PathBuf::from(text), true),
+        Some(Prompt {
+            kind: PromptKind::CloseUnsaved(id),
+            text,
+        }) => {
+            match text.trim().to_lowercase().as_str() {
+                "y" | "yes" => {
+                    if let Some(index) = state.buffers.iter().position(|b| b.id == id) {
+                        close_buffer(state, index);
+                    }
+                }
+                _ => {
+                    state.status_message = Some("Did not close the buffer".to_owned());
+                }
+            }
+            Cmd::NoCmd
+        }
+        Some(Prompt {
+            kind: PromptKind::GoTo,
+            text,
+        }) => {
+            go_to(state, &text);
+            Cmd::NoCmd
+        }
+        Some(Prompt {
+            kind: PromptKind::Find,
+            ..
+        }) => {
+            // The cursor is already on the match, so all that is left is to stop highlighting.
+            state.search_matches.clear();
+            Cmd::NoCmd
+        }
+        Some(Prompt {
+            kind: PromptKind::ReplacePattern,
+            text,
+        }) => {
+            state.search_matches.clear();
+            match state.search_error.take() {
+                Some(error) => {
+                    state.status_message = Some(error);
+                }
+                None => {
+                    state.prompt = Some(Prompt::new(PromptKind::ReplaceWith(text)));
+                }
+            }
+            Cmd::NoCmd
+        }
+        Some(Prompt {
+            kind: PromptKind::ConvertLineEndings,
+            text,
+        }) => {
+            state.status_message = match LineEnding::from_name(&text) {
+                Some(target) => state.current_buffer_mut().map(|b| {
+                    let count = b.convert_line_endings(target);
+                    format!("Converted {} line endings to {}", count, target)
+                }),
+                None => Some(format!("Unknown line ending: {}", text)),
+            };
+            Cmd::NoCmd
+        }
+        Some(Prompt {
+            kind: PromptKind::LineBreaks,
+            text,
+        }) => {
+            match LineBreaks::from_name(&text) {
+                Some(line_breaks) => {
+                    if let Some(b) = state.current_buffer_mut() {
+                        b.set_line_breaks(line_breaks);
+                    }
+                }
+                None => {
+                    state.status_message = Some(format!("Unknown line breaks: {}", text));
+                }
+            }
+            Cmd::NoCmd
+        }
+        None => Cmd::NoCmd,
+    }
+}
+
+/// `regex::Error`s can span several lines, pointing at the problem in the pattern, but the status
+/// line only has room for the last line, which says what the problem is.
+fn regex_error_summary(error: &impl std::fmt::Display) -> String {
+    let error = error.to_string();
+    error.lines().last().unwrap_or_default().trim().to_owned()
+}
+
+fn select_match(b: &mut TextBuffer, (start, end): (Position, Position)) {
+    let mut cursor = Cursor::new(end);
+    cursor.highlight_position = Some(start);
+    *b.cursors_mut() = Vec1::new(cursor);
+}
+
+fn search_from_origin(state: &mut State) {
+    let origin = state.search_origin;
+    let query = &state.search_query;
+    let buffer_index = state.current_burrer_index;
+    let b = match state.buffers.get_mut(buffer_index) {
+        Some(b) => &mut b.text_buffer,
+        None => return,
+    };
+
+    let matches = b.find_all(query);
+    let nearest = matches
+        .iter()
+        .find(|(start, _)| *start >= origin)
+        .or_else(|| matches.first());
+    if let Some(&m) = nearest {
+        select_match(b, m);
+    }
+
+    state.search_matches = matches;
+}
+
+/// Selects the next match for the search query after the primary cursor, or the previous one
+/// before it, wrapping around the ends of the buffer.
+fn find_next(state: &mut State, forward: bool) {
+    if state.search_query.is_empty() {
+        return;
+    }
+    let query = &state.search_query;
+    let buffer_index = state.current_burrer_index;
+    let b = match state.buffers.get_mut(buffer_index) {
+        Some(b) => &mut b.text_buffer,
+        None => return,
+    };
+
+    let matches = b.find_all(query);
+    let cursor = b.cursors().first();
+    let other_end = cursor.highlight_position.unwrap_or(cursor.position);
+    let selection_min = std::cmp::min(cursor.position, other_end);
+    let selection_max = std::cmp::max(cursor.position, other_end);
+
+    let found = if forward {
+        matches
+            .iter()
+            .find(|(start, _)| *start >= selection_max)
+            .or_else(|| matches.first())
+    } else {
+        matches
+            .iter()
+            .rev()
+            .find(|(start, _)| *start < selection_min)
+            .or_else(|| matches.last())
+    };
+
+    match found {
+        Some(&m) => select_match(b, m),
+        None => {
+            state.status_message = Some(format!("No matches for \"{}\"", query));
+        }
+    }
+
+    if state.prompt.is_some() {
+        state.search_matches = matches;
+    }
+}
+
+/// The places a go to prompt can send the cursors.
+#[derive(Debug)]
+enum GoToTarget {
+    /// Counting from 1.
+    Word(usize),
+    /// Both counting from 1, like the command line `path:line:col` form.
+    LineColumn(usize, Option<usize>),
+    Char(AbsoluteCharOffset),
+    Byte(ByteIndex),
+    Percentage(f64),
+}
+
+/// Accepts `N` for the Nth word, `line:col` or `line:`, `cN` for a char offset, `bN` for a byte
+/// offset and `N%` for a percentage of the way through the buffer.
+fn parse_go_to(text: &str) -> Option<GoToTarget> {
+    let text = text.trim();
+
+    if text.ends_with('%') {
+        let percentage: f64 = text[..text.len() - 1].trim().parse().ok()?;
+        if percentage.is_finite() {
+            return Some(GoToTarget::Percentage(percentage.max(0.0).min(100.0)));
+        }
+        return None;
+    }
+    if text.starts_with('c') {
+        return text[1..]
+            .parse()
+            .ok()
+            .map(|o| GoToTarget::Char(AbsoluteCharOffset(o)));
+    }
+    if text.starts_with('b') {
+        return text[1..]
+            .parse()
+            .ok()
+            .map(|i| GoToTarget::Byte(ByteIndex(i)));
+    }
+    if let Some(colon_index) = text.find(':') {
+        let line = text[..colon_index].parse().ok()?;
+        let column = &text[colon_index + 1..];
+        let column = if column.is_empty() {
+            None
+        } else {
+            Some(column.parse().ok()?)
+        };
+        return Some(GoToTarget::LineColumn(line, column));
+    }
+
+    text.parse().ok().map(GoToTarget::Word)
+}
+
+/// Replaces the cursors with a single one at the place `text` describes.
+fn go_to(state: &mut State, text: &str) {
+    let position = state
+        .current_buffer()
+        .and_then(|b| match parse_go_to(text)? {
+            GoToTarget::Word(n) => b.nth_word_start(n.checked_sub(1)?),
+            GoToTarget::LineColumn(line, column) => {
+                b.nearest_valid_position_on_same_line(Position {
+                    line: line.saturating_sub(1),
+                    offset: CharOffset(column.unwrap_or(1).saturating_sub(1)),
+                })
+            }
+            GoToTarget::Char(offset) => b.char_offset_to_pos(offset),
+            GoToTarget::Byte(index) => b.byte_index_to_pos(index),
+            GoToTarget::Percentage(percentage) => {
+                let offset = (b.len_chars() as f64 * percentage / 100.0).round() as usize;
+                b.char_offset_to_pos(AbsoluteCharOffset(offset))
+            }
+        });
+
+    match position {
+        Some(position) => {
+            if let Some(b) = state.current_buffer_mut() {
+                *b.cursors_mut() = Vec1::new(Cursor::new(position));
+            }
+        }
+        None => {
+            state.status_message = Some(format!("Could not go to \"{}\"", text));
+        }
+    }
+}
+
+/// Whether we know enough about the screen to scroll to a position on it.
+fn is_sized(state: &State) -> bool {
+    state.screen_w > 0.0 && state.screen_h > 0.0 && state.text_char_dim.h > 0.0
+}
+
+/// Scrolls just enough to put the primary cursor inside the margin from `ScrollSettings`, or
+/// into the middle of the screen horizontally, if that setting is on.
+fn scroll_to_primary_cursor(state: &mut State) {
+    let position = match state.current_buffer() {
+        Some(b) => b.cursors().first().position,
+        None => return,
+    };
+    let ScrollSettings {
+        margin,
+        center_horizontally,
+    } = state.scroll_settings;
+    let CharDim { w, h } = state.text_char_dim;
+    let ScreenSpaceXY { x, y } =
+        position_to_screen_space(position, state.text_char_dim, (0.0, 0.0));
+
+    state.scroll_x = if center_horizontally {
+        (state.screen_w - w) / 2.0 - x
+    } else {
+        scroll_to_include(state.scroll_x, x, w, state.screen_w, margin)
+    };
+    state.scroll_y = scroll_to_include(
+        state.scroll_y,
+        y,
+        h,
+        state.screen_h - state.status_char_dim.h,
+        margin,
+    );
+}
+
+/// Returns the scroll amount along one axis that puts the character at unscrolled coordinate
+/// `coord`, of size `dim`, at least `margin` characters away from both ends of `length`. We
+/// avoid scrolling past the start of the buffer to satisfy the margin, since there is nothing
+/// there to see.
+fn scroll_to_include(scroll: f32, coord: f32, dim: f32, length: f32, margin: usize) -> f32 {
+    if dim <= 0.0 || length < dim {
+        return scroll;
+    }
+    let visible_count = (length / dim).floor();
+    #[allow(clippy::cast_precision_loss)]
+    let margin = (margin as f32).min(((visible_count - 1.0) / 2.0).floor()) * dim;
+
+    if coord + scroll < margin {
+        scroll.max((margin - coord).min(0.0))
+    } else if coord + scroll + dim > length - margin {
+        length - margin - dim - coord
+    } else {
+        scroll
+    }
+}
+
+/// Selects the buffer for `path` if it is already open. Otherwise the contents go into a new
+/// buffer, unless the current buffer is an empty, never saved one, in which case it is reused.
+fn load_file(state: &mut State, path: PathBuf, contents: String) {
+    if let Some(index) = state
+        .buffers
+        .iter()
+        .position(|b| b.path.as_ref() == Some(&path))
+    {
+        state.current_burrer_index = index;
+        return;
+    }
+
+    let buffer = EditorBuffer {
+        path: Some(path),
+        id: state.next_buffer_id(),
+        ..EditorBuffer::from(TextBuffer::from(contents))
+    };
+
+    match state.buffers.get_mut(state.current_burrer_index) {
+        Some(current) if current.path.is_none() && current.text_buffer.chars().next().is_none() => {
+            *current = buffer;
+        }
+        _ => {
+            state.buffers.push(buffer);
+            state.current_burrer_index = state.buffers.len() - 1;
+        }
+    }
+}
+
+fn close_buffer(state: &mut State, index: usize) {
+    let mut buffers = std::mem::replace(&mut state.buffers, Vec1::new(d!())).into_vec();
+    if index < buffers.len() {
+        buffers.remove(index);
+    }
+
+    // There is always at least one buffer, so closing the last one leaves an empty one behind.
+    state.buffers = match Vec1::try_from_vec(buffers) {
+        Ok(buffers) => buffers,
+        Err(_) => Vec1::new(EditorBuffer {
+            id: state.next_buffer_id(),
+            ..d!()
+        }),
+    };
+
+    if index < state.current_burrer_index {
+        state.current_burrer_index -= 1;
+    }
+    if state.current_burrer_index >= state.buffers.len() {
+        state.current_burrer_index = state.buffers.len() - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,868 @@
+use super::*;
+use platform_types::{pos, BufferViewKind, Sizes};
+use std::fmt::Write as _;
+
+/// Set this, to anything, to have the snapshot tests write what they got into their expected
+/// files, instead of comparing against them.
+const REWRITE_SNAPSHOTS: &str = "REWRITE_SNAPSHOTS";
+
+const SCREEN_W: f32 = 320.0;
+const SCREEN_H: f32 = 240.0;
+const TEXT_CHAR_DIM: CharDim = CharDim { w: 16.0, h: 32.0 };
+const STATUS_CHAR_DIM: CharDim = CharDim { w: 8.0, h: 16.0 };
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("snapshots")
+        .join(format!("{}.txt", name))
+}
+
+/// Feeds `inputs` to a new `State`, after telling it the screen is `SCREEN_W` by `SCREEN_H`, and
+/// returns the last `View`. Any `Cmd`s are dropped, so inputs that need file I/O do not belong
+/// here.
+fn view_after(inputs: Vec<Input>) -> View {
+    let mut state = State::new();
+    let mut output = update_and_render(
+        &mut state,
+        Input::SetSizes(Sizes! {
+            screen_w: SCREEN_W,
+            screen_h: SCREEN_H,
+            text_char_dim: TEXT_CHAR_DIM,
+            status_char_dim: STATUS_CHAR_DIM,
+        }),
+    );
+
+    for input in inputs {
+        output = update_and_render(&mut state, input);
+    }
+
+    output.0
+}
+
+/// Writes out everything `render_view` decides about each `BufferView`, except the colours,
+/// which do not change. Each line of `chars` is written escaped and quoted, so that whitespace,
+/// and any control characters, are easy to see.
+fn view_to_snapshot(view: &View) -> String {
+    let mut output = String::new();
+
+    for buffer in view.buffers.iter() {
+        let &BufferView {
+            kind,
+            screen_position,
+            bounds,
+            chars_start,
+            ref highlights,
+            ..
+        } = buffer;
+        let kind = match kind {
+            BufferViewKind::Edit => "Edit",
+            BufferViewKind::Cursor => "Cursor",
+            BufferViewKind::StatusLine => "StatusLine",
+        };
+        // `write!` to a `String` cannot fail.
+        let _ = writeln!(output, "{}", kind);
+        let _ = writeln!(output, "    screen_position: {:?}", screen_position);
+        let _ = writeln!(output, "    bounds: {:?}", bounds);
+        let _ = writeln!(output, "    chars_start: {}", chars_start);
+        let _ = write!(output, "    highlights:");
+        for highlight in highlights.iter() {
+            let (min, max) = highlight.get();
+            let _ = write!(output, " {}-{}", min, max);
+        }
+        let _ = writeln!(output);
+        let _ = writeln!(output, "    chars:");
+        for line in buffer.lines() {
+            let _ = writeln!(output, "        {:?}", line);
+        }
+    }
+
+    output
+}
+
+/// Compares the snapshot of the `View` after `inputs` with the one in `snapshots/{name}.txt`,
+/// or, if the `REWRITE_SNAPSHOTS` environment variable is set, overwrites that file with it.
+fn assert_snapshot(name: &str, inputs: Vec<Input>) {
+    let actual = view_to_snapshot(&view_after(inputs));
+    let path = snapshot_path(name);
+
+    if std::env::var_os(REWRITE_SNAPSHOTS).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Could not read {}: {}\nRun with {} set to create it.",
+            path.display(),
+            e,
+            REWRITE_SNAPSHOTS
+        )
+    });
+    // Git may have changed the line endings on checkout.
+    let expected = expected.replace("\r\n", "\n");
+
+    assert!(
+        expected == actual,
+        "{} does not match. Run with {} set to accept the new output.\nexpected:\n{}\nactual:\n{}",
+        path.display(),
+        REWRITE_SNAPSHOTS,
+        expected,
+        actual
+    );
+}
+
+fn insert_str(s: &str) -> Vec<Input> {
+    s.chars()
+        .map(|c| {
+            if c == '\n' {
+                Input::InsertLineBreak
+            } else {
+                Input::Insert(c)
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn snapshot_empty_buffer() {
+    assert_snapshot("empty_buffer", vec![]);
+}
+
+#[test]
+fn snapshot_typed_lines() {
+    assert_snapshot("typed_lines", insert_str("hello\nworld"));
+}
+
+#[test]
+fn snapshot_crlf_line_breaks() {
+    // `Insert` puts in exactly the char given, unlike `InsertLineBreak`.
+    let inputs = "one\r\ntwo\rthree\n".chars().map(Input::Insert).collect();
+    assert_snapshot("crlf_line_breaks", inputs);
+}
+
+#[test]
+fn snapshot_selection_across_lines() {
+    let mut inputs = insert_str("hello\nworld\n!");
+    inputs.push(Input::MoveAllCursors(Move::Left));
+    inputs.push(Input::ExtendSelectionForAllCursors(Move::Up));
+    inputs.push(Input::ExtendSelectionForAllCursors(Move::Up));
+    assert_snapshot("selection_across_lines", inputs);
+}
+
+#[test]
+fn snapshot_multiple_cursors() {
+    let mut inputs = insert_str("abc\ndef\nghi");
+    inputs.push(Input::MoveAllCursors(Move::ToBufferStart));
+    // Clicking halfway down a line, where a column starts, so there is no question of which
+    // position is meant.
+    let CharDim { w, h } = TEXT_CHAR_DIM;
+    inputs.push(Input::AddCursor(ScreenSpaceXY {
+        x: 1.0 * w,
+        y: 1.5 * h,
+    }));
+    inputs.push(Input::AddCursor(ScreenSpaceXY {
+        x: 2.0 * w,
+        y: 2.5 * h,
+    }));
+    inputs.push(Input::ExtendSelectionForAllCursors(Move::Right));
+    assert_snapshot("multiple_cursors", inputs);
+}
+
+#[test]
+fn snapshot_scrolled_part_way_through_long_lines() {
+    let line = "0123456789".repeat(10);
+    let mut inputs = insert_str(&format!("{}\n{}\n{}", line, line, line));
+    inputs.push(Input::MoveAllCursors(Move::ToBufferStart));
+    inputs.push(Input::ScrollHorizontally(-50.0 * TEXT_CHAR_DIM.w));
+    inputs.push(Input::ScrollVertically(TEXT_CHAR_DIM.h));
+    assert_snapshot("scrolled_part_way_through_long_lines", inputs);
+}
+
+#[test]
+fn snapshot_find_prompt_highlights_matches() {
+    let mut inputs = insert_str("one two one\ntwo one");
+    inputs.push(Input::Find);
+    inputs.extend(insert_str("one"));
+    assert_snapshot("find_prompt_highlights_matches", inputs);
+}
+
+#[test]
+fn snapshot_cursor_behind_the_status_line() {
+    // The screen fits 7 lines and a bit, so the last line here ends up under the status line.
+    // This is the "cursor being visible through status line" item in `design/TODO.md`.
+    let mut inputs = insert_str(&"line\n".repeat(7));
+    inputs.push(Input::ResetScroll);
+    assert_snapshot("cursor_behind_the_status_line", inputs);
+}
+
+/// Feeds `inputs` to `state`, and returns the `Cmd` from the last one.
+fn cmd_after(state: &mut State, inputs: Vec<Input>) -> Cmd {
+    let mut cmd = Cmd::NoCmd;
+    for input in inputs {
+        cmd = update_and_render(state, input).1;
+    }
+    cmd
+}
+
+fn current_path(state: &State) -> Option<PathBuf> {
+    state.buffers[state.current_burrer_index].path.clone()
+}
+
+#[test]
+fn submitting_the_open_prompt_asks_for_the_file() {
+    let mut state = State::new();
+    let mut inputs = vec![Input::Open];
+    inputs.extend(insert_str("a.txt\n"));
+
+    match cmd_after(&mut state, inputs) {
+        Cmd::LoadFile(path) => assert_eq!(path, PathBuf::from("a.txt")),
+        cmd => panic!("{:?}", cmd),
+    }
+    assert!(state.prompt.is_none());
+}
+
+#[test]
+fn loaded_file_replaces_the_empty_scratch_buffer_and_is_not_opened_twice() {
+    let mut state = State::new();
+    let loaded = || Input::LoadedFile(PathBuf::from("a.txt"), "abc".to_owned());
+
+    cmd_after(&mut state, vec![loaded()]);
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_path(&state), Some(PathBuf::from("a.txt")));
+    assert_eq!(String::from(state.current_buffer().unwrap()), "abc");
+
+    cmd_after(&mut state, vec![Input::NewScratchBuffer, loaded()]);
+
+    assert_eq!(state.buffers.len(), 2);
+    assert_eq!(state.current_burrer_index, 0);
+}
+
+#[test]
+fn file_error_is_shown_in_the_status_message() {
+    let mut state = State::new();
+
+    cmd_after(
+        &mut state,
+        vec![Input::FileError(
+            None,
+            PathBuf::from("a.txt"),
+            "Not found".to_owned(),
+        )],
+    );
+
+    assert_eq!(state.status_message, Some("a.txt: Not found".to_owned()));
+}
+
+#[test]
+fn saving_a_buffer_without_a_path_asks_for_one_then_saves_there() {
+    let mut state = State::new();
+    let mut inputs = insert_str("abc");
+    inputs.push(Input::Save);
+    cmd_after(&mut state, inputs);
+
+    assert!(state.prompt.is_some());
+
+    let id = match cmd_after(&mut state, insert_str("a.txt\n")) {
+        Cmd::SaveFileAs(id, path, contents) => {
+            assert_eq!(path, PathBuf::from("a.txt"));
+            assert_eq!(contents, "abc");
+            id
+        }
+        cmd => panic!("{:?}", cmd),
+    };
+    assert_eq!(current_path(&state), None);
+
+    match cmd_after(
+        &mut state,
+        vec![Input::SavedAs(id, PathBuf::from("a.txt")), Input::Save],
+    ) {
+        Cmd::SaveFile(saved_id, path, contents) => {
+            assert_eq!(saved_id, id);
+            assert_eq!(path, PathBuf::from("a.txt"));
+            assert_eq!(contents, "abc");
+        }
+        cmd => panic!("{:?}", cmd),
+    }
+}
+
+#[test]
+fn saved_as_updates_the_buffer_that_was_saved_even_if_an_earlier_one_was_closed() {
+    let mut state = State::new();
+    let mut inputs = vec![Input::NewScratchBuffer, Input::SaveAs];
+    inputs.extend(insert_str("b.txt\n"));
+
+    let id = match cmd_after(&mut state, inputs) {
+        Cmd::SaveFileAs(id, _, _) => id,
+        cmd => panic!("{:?}", cmd),
+    };
+
+    // The buffer being saved moves from index 1 to index 0.
+    cmd_after(
+        &mut state,
+        vec![
+            Input::SelectBuffer(0),
+            Input::CloseBuffer,
+            Input::SavedAs(id, PathBuf::from("b.txt")),
+        ],
+    );
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_path(&state), Some(PathBuf::from("b.txt")));
+}
+
+#[test]
+fn saved_as_for_a_closed_buffer_does_not_change_the_others() {
+    let mut state = State::new();
+    let mut inputs = vec![Input::NewScratchBuffer, Input::SaveAs];
+    inputs.extend(insert_str("b.txt\n"));
+
+    let id = match cmd_after(&mut state, inputs) {
+        Cmd::SaveFileAs(id, _, _) => id,
+        cmd => panic!("{:?}", cmd),
+    };
+
+    cmd_after(
+        &mut state,
+        vec![
+            Input::CloseBuffer,
+            Input::SavedAs(id, PathBuf::from("b.txt")),
+        ],
+    );
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_path(&state), None);
+}
+
+/// A state with a buffer for each of `texts`, with the last one selected.
+fn state_with_buffers(texts: &[&str]) -> State {
+    let mut state = State::new();
+    for (i, text) in texts.iter().enumerate() {
+        let path = PathBuf::from(format!("{}.txt", i));
+        cmd_after(&mut state, vec![Input::LoadedFile(path, text.to_string())]);
+    }
+    state
+}
+
+fn current_text(state: &State) -> String {
+    state.current_buffer().map(String::from).unwrap_or_default()
+}
+
+#[test]
+fn next_and_previous_buffer_wrap_around() {
+    let mut state = state_with_buffers(&["a", "b", "c"]);
+
+    cmd_after(&mut state, vec![Input::NextBuffer]);
+    assert_eq!(current_text(&state), "a");
+    cmd_after(&mut state, vec![Input::NextBuffer]);
+    assert_eq!(current_text(&state), "b");
+
+    cmd_after(
+        &mut state,
+        vec![Input::PreviousBuffer, Input::PreviousBuffer],
+    );
+    assert_eq!(current_text(&state), "c");
+}
+
+#[test]
+fn select_buffer_ignores_indexes_past_the_end() {
+    let mut state = state_with_buffers(&["a", "b", "c"]);
+
+    cmd_after(&mut state, vec![Input::SelectBuffer(0)]);
+    assert_eq!(current_text(&state), "a");
+
+    cmd_after(&mut state, vec![Input::SelectBuffer(3)]);
+    assert_eq!(current_text(&state), "a");
+}
+
+#[test]
+fn closing_a_buffer_selects_the_one_after_it() {
+    let mut state = state_with_buffers(&["a", "b", "c"]);
+
+    cmd_after(&mut state, vec![Input::SelectBuffer(1), Input::CloseBuffer]);
+
+    assert_eq!(state.buffers.len(), 2);
+    assert_eq!(current_text(&state), "c");
+
+    cmd_after(&mut state, vec![Input::CloseBuffer]);
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_text(&state), "a");
+}
+
+#[test]
+fn closing_the_last_buffer_leaves_an_empty_one() {
+    let mut state = state_with_buffers(&["a"]);
+
+    cmd_after(&mut state, vec![Input::CloseBuffer]);
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_text(&state), "");
+    assert_eq!(current_path(&state), None);
+}
+
+#[test]
+fn closing_a_buffer_with_unsaved_changes_asks_first() {
+    let mut state = state_with_buffers(&["a", "b"]);
+    let mut inputs = insert_str("!");
+    inputs.push(Input::CloseBuffer);
+    cmd_after(&mut state, inputs);
+
+    assert_eq!(state.buffers.len(), 2);
+    assert!(state.prompt.is_some());
+
+    cmd_after(&mut state, insert_str("no\n"));
+
+    assert_eq!(state.buffers.len(), 2);
+    assert_eq!(current_text(&state), "!b");
+
+    let mut inputs = vec![Input::CloseBuffer];
+    inputs.extend(insert_str("yes\n"));
+    cmd_after(&mut state, inputs);
+
+    assert_eq!(state.buffers.len(), 1);
+    assert_eq!(current_text(&state), "a");
+}
+
+#[test]
+fn closing_a_buffer_does_not_ask_once_changes_are_undone_or_saved() {
+    let mut state = state_with_buffers(&["a", "b"]);
+    let mut inputs = insert_str("!");
+    inputs.push(Input::Undo);
+    inputs.push(Input::CloseBuffer);
+    cmd_after(&mut state, inputs);
+
+    assert!(state.prompt.is_none());
+    assert_eq!(state.buffers.len(), 1);
+
+    let mut inputs = insert_str("!");
+    inputs.push(Input::Save);
+    let id = match cmd_after(&mut state, inputs) {
+        Cmd::SaveFile(id, _, _) => id,
+        cmd => panic!("{:?}", cmd),
+    };
+    cmd_after(
+        &mut state,
+        vec![Input::Saved(id, PathBuf::from("0.txt")), Input::CloseBuffer],
+    );
+
+    assert!(state.prompt.is_none());
+    assert_eq!(current_text(&state), "");
+}
+
+#[test]
+fn changes_made_while_saving_are_still_unsaved() {
+    let mut state = state_with_buffers(&["a"]);
+    let mut inputs = insert_str("!");
+    inputs.push(Input::Save);
+    let id = match cmd_after(&mut state, inputs) {
+        Cmd::SaveFile(id, _, _) => id,
+        cmd => panic!("{:?}", cmd),
+    };
+    let mut inputs = insert_str("?");
+    inputs.push(Input::Saved(id, PathBuf::from("0.txt")));
+    inputs.push(Input::CloseBuffer);
+    cmd_after(&mut state, inputs);
+
+    assert!(state.prompt.is_some());
+    assert_eq!(current_text(&state), "!?a");
+}
+
+#[test]
+fn a_failed_save_leaves_the_changes_unsaved() {
+    let mut state = state_with_buffers(&["a"]);
+    let mut inputs = insert_str("!");
+    inputs.push(Input::Save);
+    let (id, path) = match cmd_after(&mut state, inputs) {
+        Cmd::SaveFile(id, path, _) => (id, path),
+        cmd => panic!("{:?}", cmd),
+    };
+    cmd_after(
+        &mut state,
+        vec![Input::FileError(
+            Some(id),
+            path,
+            "Permission denied".to_owned(),
+        )],
+    );
+
+    let b = &state.buffers[state.current_burrer_index];
+    assert_eq!(b.saving_version, b.saved_version);
+    cmd_after(&mut state, vec![Input::CloseBuffer]);
+    assert!(state.prompt.is_some());
+}
+
+#[test]
+fn parse_go_to_accepts_each_kind_of_target() {
+    assert!(match parse_go_to("3") {
+        Some(GoToTarget::Word(3)) => true,
+        _ => false,
+    });
+    assert!(match parse_go_to(" 12:5 ") {
+        Some(GoToTarget::LineColumn(12, Some(5))) => true,
+        _ => false,
+    });
+    assert!(match parse_go_to("12:") {
+        Some(GoToTarget::LineColumn(12, None)) => true,
+        _ => false,
+    });
+    assert!(match parse_go_to("c40") {
+        Some(GoToTarget::Char(AbsoluteCharOffset(40))) => true,
+        _ => false,
+    });
+    assert!(match parse_go_to("b40") {
+        Some(GoToTarget::Byte(ByteIndex(40))) => true,
+        _ => false,
+    });
+    assert!(match parse_go_to("50 %") {
+        Some(GoToTarget::Percentage(p)) => p == 50.0,
+        _ => false,
+    });
+}
+
+#[test]
+fn parse_go_to_clamps_percentages() {
+    assert!(match parse_go_to("150%") {
+        Some(GoToTarget::Percentage(p)) => p == 100.0,
+        _ => false,
+    });
+    assert!(match parse_go_to("-5%") {
+        Some(GoToTarget::Percentage(p)) => p == 0.0,
+        _ => false,
+    });
+}
+
+#[test]
+fn parse_go_to_rejects_malformed_targets() {
+    for text in &[
+        "", "word", "-1", "1.5", ":5", "3:x", "3:4:5", "c", "cx", "b-1", "%", "inf%", "NaN%",
+    ] {
+        assert!(parse_go_to(text).is_none(), "{:?}", text);
+    }
+}
+
+fn go_to_in(text: &str, target: &str) -> (Position, Option<String>) {
+    let mut state: State = text.into();
+    let mut inputs = vec![Input::GoTo];
+    inputs.extend(insert_str(target));
+    inputs.push(Input::InsertLineBreak);
+    cmd_after(&mut state, inputs);
+
+    let position = state.current_buffer().unwrap().cursors().first().position;
+    (position, state.status_message.clone())
+}
+
+#[test]
+fn go_to_moves_the_cursor_to_valid_targets() {
+    assert_eq!(go_to_in("one two\nthree", "3"), (pos! {l 1 o 0}, None));
+    assert_eq!(go_to_in("one two\nthree", "2:3"), (pos! {l 1 o 2}, None));
+    assert_eq!(go_to_in("one two\nthree", "c4"), (pos! {l 0 o 4}, None));
+    assert_eq!(go_to_in("one two\nthree", "100%"), (pos! {l 1 o 5}, None));
+}
+
+#[test]
+fn go_to_reports_out_of_range_targets_and_leaves_the_cursor_alone() {
+    for target in &["4", "0", "3:1", "c100", "b100"] {
+        let (position, message) = go_to_in("one two\nthree", target);
+        assert_eq!(position, pos! {l 0 o 0}, "{:?}", target);
+        assert_eq!(
+            message,
+            Some(format!("Could not go to \"{}\"", target)),
+            "{:?}",
+            target
+        );
+    }
+}
+
+#[test]
+fn go_to_a_column_past_the_end_of_the_line_goes_to_the_end() {
+    assert_eq!(go_to_in("one two\nthree", "2:100"), (pos! {l 1 o 5}, None));
+    // Lines before the last one end with a line break, which the cursor should stay in front of.
+    assert_eq!(go_to_in("one two\nthree", "1:100"), (pos! {l 0 o 7}, None));
+    assert_eq!(
+        go_to_in("one\r\ntwo\r\nthree", "2:100"),
+        (pos! {l 1 o 3}, None)
+    );
+}
+
+#[test]
+fn clicking_past_the_end_of_a_crlf_line_puts_the_cursor_before_the_line_break() {
+    let mut state: State = "ab\r\ncd".into();
+    let CharDim { w, h } = TEXT_CHAR_DIM;
+    cmd_after(
+        &mut state,
+        vec![
+            Input::SetSizes(Sizes! {
+                screen_w: SCREEN_W,
+                screen_h: SCREEN_H,
+                text_char_dim: TEXT_CHAR_DIM,
+                status_char_dim: STATUS_CHAR_DIM,
+            }),
+            Input::ReplaceCursors(ScreenSpaceXY {
+                x: 10.0 * w,
+                y: 0.5 * h,
+            }),
+        ],
+    );
+    assert_eq!(primary_selection(&state), (None, pos! {l 0 o 2}));
+
+    cmd_after(&mut state, vec![Input::Insert('!')]);
+    assert_eq!(current_text(&state), "ab!\r\ncd");
+}
+
+#[test]
+fn opening_at_a_far_line_scrolls_to_it_once_the_screen_size_is_known() {
+    let mut state = State::new();
+    state.open_at(
+        PathBuf::from("far.txt"),
+        "line\n".repeat(100),
+        pos! {l 50 o 2},
+    );
+    assert_eq!(state.scroll_y, 0.0);
+
+    cmd_after(
+        &mut state,
+        vec![Input::SetSizes(Sizes! {
+            screen_w: SCREEN_W,
+            screen_h: SCREEN_H,
+            text_char_dim: TEXT_CHAR_DIM,
+            status_char_dim: STATUS_CHAR_DIM,
+        })],
+    );
+    let cursor_y = 50.0 * TEXT_CHAR_DIM.h;
+    assert!(-state.scroll_y < cursor_y, "{}", state.scroll_y);
+    assert!(
+        cursor_y + TEXT_CHAR_DIM.h <= -state.scroll_y + SCREEN_H - STATUS_CHAR_DIM.h,
+        "{}",
+        state.scroll_y
+    );
+
+    // Only the first size scrolls, so later ones leave where the user has scrolled to alone.
+    cmd_after(
+        &mut state,
+        vec![
+            Input::ResetScroll,
+            Input::SetSizes(Sizes! {
+                screen_w: SCREEN_W,
+                screen_h: SCREEN_H,
+                text_char_dim: TEXT_CHAR_DIM,
+                status_char_dim: STATUS_CHAR_DIM,
+            }),
+        ],
+    );
+    assert_eq!(state.scroll_y, 0.0);
+}
+
+// With these, 10 characters fit, and a margin of 2 characters is 20 units.
+const DIM: f32 = 10.0;
+const LENGTH: f32 = 100.0;
+
+#[test]
+fn scroll_to_include_leaves_a_visible_character_alone() {
+    assert_eq!(scroll_to_include(0.0, 50.0, DIM, LENGTH, 2), 0.0);
+    assert_eq!(scroll_to_include(-30.0, 80.0, DIM, LENGTH, 2), -30.0);
+}
+
+#[test]
+fn scroll_to_include_accepts_characters_right_on_the_margins() {
+    assert_eq!(scroll_to_include(0.0, 20.0, DIM, LENGTH, 2), 0.0);
+    assert_eq!(scroll_to_include(0.0, 70.0, DIM, LENGTH, 2), 0.0);
+}
+
+#[test]
+fn scroll_to_include_scrolls_just_enough_to_get_out_of_the_margins() {
+    assert_eq!(scroll_to_include(-20.0, 30.0, DIM, LENGTH, 2), -10.0);
+    assert_eq!(scroll_to_include(0.0, 71.0, DIM, LENGTH, 2), -1.0);
+    assert_eq!(scroll_to_include(0.0, 200.0, DIM, LENGTH, 2), -130.0);
+}
+
+#[test]
+fn scroll_to_include_does_not_scroll_past_the_start_for_the_margin() {
+    assert_eq!(scroll_to_include(0.0, 10.0, DIM, LENGTH, 2), 0.0);
+    assert_eq!(scroll_to_include(-50.0, 0.0, DIM, LENGTH, 2), 0.0);
+}
+
+#[test]
+fn scroll_to_include_shrinks_margins_that_do_not_fit() {
+    // Only 4 characters of margin fit on each side of one in the middle.
+    assert_eq!(scroll_to_include(0.0, 50.0, DIM, LENGTH, 10), 0.0);
+    assert_eq!(scroll_to_include(0.0, 51.0, DIM, LENGTH, 10), -1.0);
+    assert_eq!(scroll_to_include(-70.0, 100.0, DIM, LENGTH, 10), -60.0);
+}
+
+#[test]
+fn scroll_to_include_does_nothing_when_no_character_fits() {
+    assert_eq!(scroll_to_include(-5.0, 500.0, DIM, DIM - 1.0, 2), -5.0);
+    assert_eq!(scroll_to_include(-5.0, 500.0, 0.0, LENGTH, 2), -5.0);
+}
+
+fn move_cursor_to_end(state: &mut State) {
+    let b = state.current_buffer_mut().unwrap();
+    let end = b
+        .char_offset_to_pos(AbsoluteCharOffset(b.len_chars()))
+        .unwrap();
+    *b.cursors_mut() = Vec1::new(Cursor::new(end));
+}
+
+fn primary_selection(state: &State) -> (Option<Position>, Position) {
+    let cursor = state.current_buffer().unwrap().cursors().first().clone();
+    (cursor.highlight_position, cursor.position)
+}
+
+#[test]
+fn find_searches_again_for_the_same_query_in_a_new_prompt() {
+    let mut state: State = "one two one".into();
+    let mut inputs = vec![Input::Find];
+    inputs.extend(insert_str("one\n"));
+    cmd_after(&mut state, inputs);
+
+    move_cursor_to_end(&mut state);
+    let mut inputs = vec![Input::Find];
+    inputs.extend(insert_str("one"));
+    cmd_after(&mut state, inputs);
+
+    // Nothing is after the cursor, so the search wraps around to the first match.
+    assert_eq!(
+        primary_selection(&state),
+        (Some(pos! {l 0 o 0}), pos! {l 0 o 3})
+    );
+    assert_eq!(state.search_matches.len(), 2);
+}
+
+#[test]
+fn find_does_not_search_again_until_the_query_changes() {
+    let mut state: State = "one two one".into();
+    let mut inputs = vec![Input::Find];
+    inputs.extend(insert_str("one"));
+    cmd_after(&mut state, inputs);
+
+    // If this searched again, it would select the first match after the search origin.
+    move_cursor_to_end(&mut state);
+    cmd_after(
+        &mut state,
+        vec![Input::SetMousePos(ScreenSpaceXY { x: 1.0, y: 1.0 })],
+    );
+    assert_eq!(primary_selection(&state), (None, pos! {l 0 o 11}));
+
+    cmd_after(&mut state, vec![Input::Delete]);
+    assert_eq!(
+        primary_selection(&state),
+        (Some(pos! {l 0 o 0}), pos! {l 0 o 2})
+    );
+}
+
+#[test]
+fn escape_cancels_a_replace_with_an_empty_replacement() {
+    let mut state: State = "one two one".into();
+    let mut inputs = vec![Input::Replace];
+    inputs.extend(insert_str("one\n"));
+    inputs.push(Input::CollapseCursors);
+    cmd_after(&mut state, inputs);
+
+    assert!(state.prompt.is_none());
+    assert!(state.search_matches.is_empty());
+    assert_eq!(current_text(&state), "one two one");
+}
+
+#[test]
+fn submitting_an_empty_replacement_replaces_with_nothing() {
+    let mut state: State = "one two one".into();
+    let mut inputs = vec![Input::Replace];
+    inputs.extend(insert_str("one\n\n"));
+    cmd_after(&mut state, inputs);
+
+    assert!(state.prompt.is_none());
+    assert_eq!(current_text(&state), " two ");
+}
+
+#[test]
+fn escape_cancels_a_prompt_and_only_collapses_cursors_once_the_prompt_is_gone() {
+    let mut state: State = "one\ntwo".into();
+    cmd_after(
+        &mut state,
+        vec![Input::AddCursorBelow, Input::GoTo, Input::CollapseCursors],
+    );
+
+    assert!(state.prompt.is_none());
+    assert_eq!(state.current_buffer().unwrap().cursors().len(), 2);
+
+    cmd_after(&mut state, vec![Input::CollapseCursors]);
+
+    assert_eq!(state.current_buffer().unwrap().cursors().len(), 1);
+}
+
+#[test]
+fn escape_cancels_a_find_and_puts_the_cursor_back_where_the_search_started() {
+    let mut state: State = "one two one".into();
+    cmd_after(&mut state, vec![Input::MoveAllCursors(Move::Right)]);
+    let mut inputs = vec![Input::Find];
+    inputs.extend(insert_str("one"));
+    cmd_after(&mut state, inputs);
+    assert_eq!(
+        primary_selection(&state),
+        (Some(pos! {l 0 o 8}), pos! {l 0 o 11})
+    );
+
+    cmd_after(
+        &mut state,
+        vec![Input::AddCursorAtNextOccurrence, Input::CollapseCursors],
+    );
+
+    assert!(state.prompt.is_none());
+    assert!(state.search_matches.is_empty());
+    assert_eq!(state.current_buffer().unwrap().cursors().len(), 1);
+    assert_eq!(primary_selection(&state), (None, pos! {l 0 o 1}));
+}
+
+#[test]
+fn the_replace_prompt_finds_matches_again_only_when_the_pattern_changes() {
+    let mut state: State = "one two one".into();
+    let mut inputs = vec![Input::Replace];
+    inputs.extend(insert_str("o(n"));
+    cmd_after(&mut state, inputs);
+
+    assert!(state.search_error.is_some());
+
+    // If the matches were found again here, the error would be back.
+    state.search_error = None;
+    cmd_after(
+        &mut state,
+        vec![Input::SetMousePos(ScreenSpaceXY { x: 1.0, y: 1.0 })],
+    );
+    assert!(state.search_error.is_none());
+
+    cmd_after(&mut state, insert_str(")"));
+    assert!(state.search_error.is_none());
+    assert_eq!(state.search_matches.len(), 2);
+}
+
+#[test]
+fn deleting_in_a_prompt_deletes_back_from_the_end_of_its_text() {
+    let mut state = State::new();
+    let mut inputs = vec![Input::Open];
+    inputs.extend(insert_str("some/long path"));
+    cmd_after(&mut state, inputs);
+
+    let prompt_text = |state: &State| state.prompt.as_ref().unwrap().text.clone();
+
+    cmd_after(&mut state, vec![Input::DeleteTo(Move::ToPreviousWordStart)]);
+    assert_eq!(prompt_text(&state), "some/long ");
+
+    // There is nothing after the cursor to delete.
+    cmd_after(
+        &mut state,
+        vec![
+            Input::DeleteTo(Move::Right),
+            Input::DeleteTo(Move::ToNextWordEnd),
+            Input::DeleteTo(Move::ToLineEnd),
+        ],
+    );
+    assert_eq!(prompt_text(&state), "some/long ");
+
+    cmd_after(&mut state, vec![Input::DeleteTo(Move::Left)]);
+    assert_eq!(prompt_text(&state), "some/long");
+
+    cmd_after(&mut state, vec![Input::DeleteTo(Move::ToLineStart)]);
+    assert_eq!(prompt_text(&state), "");
+}
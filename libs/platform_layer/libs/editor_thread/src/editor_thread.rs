@@ -0,0 +1,84 @@
+use platform_types::{Cmd, Input, UpdateAndRender, UpdateAndRenderOutput};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+
+/// Runs `update_and_render` on its own thread, so the platform layer never waits on the editor.
+/// Each `Input` sent in gets exactly one `UpdateAndRenderOutput` sent back. The thread stops
+/// after an `Input::Quit`, or once the `Sender` is dropped.
+pub fn spawn(
+    update_and_render: UpdateAndRender,
+) -> (
+    Sender<Input>,
+    Receiver<UpdateAndRenderOutput>,
+    JoinHandle<()>,
+) {
+    // into the editor thread
+    let (in_tx, in_rx) = channel();
+    // out of the editor thread
+    let (out_tx, out_rx) = channel();
+
+    let join_handle = std::thread::Builder::new()
+        .name("editor".to_string())
+        .spawn(move || {
+            while let Ok(input) = in_rx.recv() {
+                if let Input::Quit = input {
+                    let _hope_it_gets_there = out_tx.send(update_and_render(input));
+                    return;
+                }
+                let _hope_it_gets_there = out_tx.send(update_and_render(input));
+            }
+        })
+        .expect("Could not start editor thread!");
+
+    (in_tx, out_rx, join_handle)
+}
+
+/// Carries out the `Cmd`. File I/O happens on a separate thread, so neither the editor thread nor
+/// the platform layer waits on the filesystem. The outcome is sent back to the editor as an
+/// `Input`. Since each platform layer has its own idea of a clipboard, that part is left to
+/// `set_clipboard`.
+///
+/// Returns whether an `Input` with the outcome will be sent back.
+pub fn handle_cmd(cmd: Cmd, in_tx: &Sender<Input>, set_clipboard: impl FnOnce(String)) -> bool {
+    use std::fs;
+
+    match cmd {
+        Cmd::NoCmd => return false,
+        Cmd::SetClipboard(s) => {
+            set_clipboard(s);
+            return false;
+        }
+        _ => {}
+    }
+
+    let in_tx = in_tx.clone();
+    let io = move || {
+        let input = match cmd {
+            Cmd::NoCmd | Cmd::SetClipboard(_) => return,
+            Cmd::LoadFile(path) => match fs::read_to_string(&path) {
+                Ok(contents) => Input::LoadedFile(path, contents),
+                Err(e) => Input::FileError(None, path, e.to_string()),
+            },
+            Cmd::SaveFile(id, path, contents) => match fs::write(&path, contents) {
+                Ok(()) => Input::Saved(id, path),
+                Err(e) => Input::FileError(Some(id), path, e.to_string()),
+            },
+            Cmd::SaveFileAs(id, path, contents) => match fs::write(&path, contents) {
+                Ok(()) => Input::SavedAs(id, path),
+                Err(e) => Input::FileError(Some(id), path, e.to_string()),
+            },
+        };
+        let _hope_it_gets_there = in_tx.send(input);
+    };
+
+    match std::thread::Builder::new()
+        .name("file io".to_string())
+        .spawn(io)
+    {
+        Ok(_) => true,
+        Err(e) => {
+            println!("Could not start file io thread:\n{}", e);
+            false
+        }
+    }
+}
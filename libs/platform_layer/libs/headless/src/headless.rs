@@ -0,0 +1,379 @@
+//! Runs the editor without a window, from a script with one `Input` on each line. Each line is
+//! written like the `Debug` output of an `Input`, except that the fields of a struct are written
+//! in order, without their names, so `SetSizes` takes `screen_w, screen_h, char_w, char_h`,
+//! inputs that take a `ScreenSpaceXY` take `x, y`, and a `BufferId` is just its number, or is
+//! left out, if it is optional and there is none. For example:
+//!
+//! ```text
+//! # Blank lines, and lines starting with `#`, are skipped.
+//! Insert('a')
+//! Paste("bc\n")
+//! ExtendSelectionForAllCursors(ToBufferStart)
+//! Copy
+//! # `Paste` on its own pastes whatever was last copied or cut.
+//! Paste
+//! ```
+//!
+//! Until the script says otherwise, each char is one pixel square, and the screen is `SCREEN_W`
+//! by `SCREEN_H` pixels, so the final `View` has all of any reasonably sized buffer in it.
+//!
+//! A line like `Screenshot("shot.png", 640, 480)` is not sent to the editor as an `Input`.
+//! Instead, the editor is told the screen is that many pixels wide and high, with chars the size
+//! the OpenGL layer uses, and the `View` it gives back is drawn into the given PNG file. Then the
+//! sizes are put back how they were.
+use macros::d;
+use platform_types::{
+    BufferId, BufferViewKind, CharDim, Input, Move, ScreenSpaceXY, Sizes, UpdateAndRender,
+    UpdateAndRenderOutput, View,
+};
+use software_renderer::{FontInfo, STATUS_SIZE, TEXT_SIZE};
+use std::io::{BufRead, Write};
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::{Chars, FromStr};
+use std::sync::mpsc::{Receiver, Sender};
+
+pub type Res<T> = Result<T, Box<dyn std::error::Error>>;
+
+pub const SCREEN_W: f32 = 65_536.0;
+pub const SCREEN_H: f32 = 16_777_216.0;
+
+/// Feeds each `Input` in `script` to the editor, waiting for any file I/O each one causes to
+/// finish before going on to the next, so that running the same script gives the same result
+/// every time. Then the final `View` is written to `output`, followed by the text of each `Edit`
+/// view in it. That text is only what the view shows, which, with the default sizes, is all of
+/// any reasonably sized buffer, but a script that scrolls, or sets smaller sizes, will get less.
+pub fn run(
+    update_and_render: UpdateAndRender,
+    script: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Res<()> {
+    let (in_tx, out_rx, join_handle) = editor_thread::spawn(update_and_render);
+    let mut clipboard = String::new();
+
+    let char_dim = CharDim { w: 1.0, h: 1.0 };
+    let mut sizes = Sizes! {
+        screen_w: SCREEN_W,
+        screen_h: SCREEN_H,
+        text_char_dim: char_dim,
+        status_char_dim: char_dim,
+    };
+    let mut view = send_and_wait(&in_tx, &out_rx, &mut clipboard, Input::SetSizes(sizes))?;
+
+    for (i, line) in script.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line_error = |e| format!("line {}: {}", i + 1, e);
+        if line.starts_with("Screenshot") {
+            let (path, width, height) = parse_screenshot(line).map_err(line_error)?;
+            let font_info = FontInfo::new(TEXT_SIZE, STATUS_SIZE)?;
+
+            let screenshot_view = send_and_wait(
+                &in_tx,
+                &out_rx,
+                &mut clipboard,
+                Input::SetSizes(font_info.sizes(width, height)),
+            )?;
+            software_renderer::render(&screenshot_view, &font_info, width, height)
+                .save_png(&path)?;
+
+            view = send_and_wait(&in_tx, &out_rx, &mut clipboard, Input::SetSizes(sizes))?;
+            continue;
+        }
+
+        let input = if line == "Paste" {
+            Input::Paste(clipboard.clone())
+        } else {
+            parse_input(line).map_err(line_error)?
+        };
+        match input {
+            Input::Quit => break,
+            Input::SetSizes(s) => sizes = s,
+            _ => {}
+        }
+
+        view = send_and_wait(&in_tx, &out_rx, &mut clipboard, input)?;
+    }
+
+    send_and_wait(&in_tx, &out_rx, &mut clipboard, Input::Quit)?;
+    join_handle
+        .join()
+        .map_err(|_| "Could not join editor thread!")?;
+
+    writeln!(output, "{:#?}", view)?;
+    for buffer in view.buffers.iter() {
+        if let BufferViewKind::Edit = buffer.kind {
+            writeln!(output, "{}", buffer.chars)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends `input`, then handles the `Cmd`s that come back until the editor has heard the outcome
+/// of all of them. Returns the last `View`.
+fn send_and_wait(
+    in_tx: &Sender<Input>,
+    out_rx: &Receiver<UpdateAndRenderOutput>,
+    clipboard: &mut String,
+    input: Input,
+) -> Res<View> {
+    let mut view = d!();
+    let mut pending = 1;
+
+    in_tx.send(input)?;
+    while pending > 0 {
+        let (v, cmd) = out_rx.recv()?;
+        pending -= 1;
+        view = v;
+
+        if editor_thread::handle_cmd(cmd, in_tx, |s| *clipboard = s) {
+            pending += 1;
+        }
+    }
+
+    Ok(view)
+}
+
+#[derive(Debug, PartialEq)]
+enum Arg {
+    Char(char),
+    Str(String),
+    /// Numbers and names are kept as written, since what they should be parsed as depends on
+    /// which input they are for.
+    Word(String),
+}
+
+/// Splits a line like `Name(arg, ...)` into its name and arguments.
+fn parse_call(line: &str) -> Result<(&str, Vec<Arg>), String> {
+    let line = line.trim();
+    let (name, args) = match line.find('(') {
+        Some(i) if line.ends_with(')') => (&line[..i], parse_args(&line[i + 1..line.len() - 1])?),
+        Some(_) => return Err(format!("Missing `)` in {}", line)),
+        None => (line, Vec::new()),
+    };
+
+    Ok((name.trim(), args))
+}
+
+/// Parses a `Screenshot` line, as described at the top of this file, into the path to save the
+/// screenshot to, and its width and height.
+fn parse_screenshot(line: &str) -> Result<(PathBuf, u32, u32), String> {
+    let (name, args) = parse_call(line)?;
+
+    use Arg::*;
+    match (name, &args[..]) {
+        ("Screenshot", [Str(path), Word(width), Word(height)]) => {
+            Ok((path.into(), parse_number(width)?, parse_number(height)?))
+        }
+        ("Screenshot", _) => Err(format!(
+            "Expected a path, width and height in {}",
+            line.trim()
+        )),
+        _ => Err(format!("Unknown input: {}", line.trim())),
+    }
+}
+
+/// Parses a single line of a script, as described at the top of this file.
+pub fn parse_input(line: &str) -> Result<Input, String> {
+    let (name, args) = parse_call(line)?;
+
+    use Arg::*;
+    Ok(match (name, &args[..]) {
+        ("None", []) => Input::None,
+        ("Quit", []) => Input::Quit,
+        ("Insert", [Char(c)]) => Input::Insert(*c),
+        ("InsertLineBreak", []) => Input::InsertLineBreak,
+        ("Delete", []) => Input::Delete,
+        ("DeleteTo", [Word(m)]) => Input::DeleteTo(parse_move(m)?),
+        ("ResetScroll", []) => Input::ResetScroll,
+        ("ScrollVertically", [Word(n)]) => Input::ScrollVertically(parse_number(n)?),
+        ("ScrollHorizontally", [Word(n)]) => Input::ScrollHorizontally(parse_number(n)?),
+        ("SetSizes", [Word(screen_w), Word(screen_h), Word(w), Word(h)]) => {
+            let char_dim = CharDim {
+                w: parse_number(w)?,
+                h: parse_number(h)?,
+            };
+            Input::SetSizes(Sizes! {
+                screen_w: parse_number::<f32>(screen_w)?,
+                screen_h: parse_number::<f32>(screen_h)?,
+                text_char_dim: char_dim,
+                status_char_dim: char_dim,
+            })
+        }
+        ("SetMousePos", [Word(x), Word(y)]) => Input::SetMousePos(parse_xy(x, y)?),
+        ("MoveAllCursors", [Word(m)]) => Input::MoveAllCursors(parse_move(m)?),
+        ("ExtendSelectionForAllCursors", [Word(m)]) => {
+            Input::ExtendSelectionForAllCursors(parse_move(m)?)
+        }
+        ("ReplaceCursors", [Word(x), Word(y)]) => Input::ReplaceCursors(parse_xy(x, y)?),
+        ("AddCursor", [Word(x), Word(y)]) => Input::AddCursor(parse_xy(x, y)?),
+        ("DragCursors", [Word(x), Word(y)]) => Input::DragCursors(parse_xy(x, y)?),
+        ("SelectWord", [Word(x), Word(y)]) => Input::SelectWord(parse_xy(x, y)?),
+        ("SelectLine", [Word(x), Word(y)]) => Input::SelectLine(parse_xy(x, y)?),
+        ("AddCursorAbove", []) => Input::AddCursorAbove,
+        ("AddCursorBelow", []) => Input::AddCursorBelow,
+        ("CollapseCursors", []) => Input::CollapseCursors,
+        ("Open", []) => Input::Open,
+        ("Save", []) => Input::Save,
+        ("SaveAs", []) => Input::SaveAs,
+        ("LoadedFile", [Str(path), Str(contents)]) => {
+            Input::LoadedFile(path.into(), contents.clone())
+        }
+        ("Saved", [Word(index), Str(path)]) => {
+            Input::Saved(BufferId(parse_number(index)?), path.into())
+        }
+        ("SavedAs", [Word(index), Str(path)]) => {
+            Input::SavedAs(BufferId(parse_number(index)?), path.into())
+        }
+        ("FileError", [Str(path), Str(error)]) => {
+            Input::FileError(None, path.into(), error.clone())
+        }
+        ("FileError", [Word(id), Str(path), Str(error)]) => Input::FileError(
+            Some(BufferId(parse_number(id)?)),
+            path.into(),
+            error.clone(),
+        ),
+        ("NextBuffer", []) => Input::NextBuffer,
+        ("PreviousBuffer", []) => Input::PreviousBuffer,
+        ("SelectBuffer", [Word(index)]) => Input::SelectBuffer(parse_number(index)?),
+        ("NewScratchBuffer", []) => Input::NewScratchBuffer,
+        ("CloseBuffer", []) => Input::CloseBuffer,
+        ("Undo", []) => Input::Undo,
+        ("Redo", []) => Input::Redo,
+        ("Cut", []) => Input::Cut,
+        ("Copy", []) => Input::Copy,
+        ("Paste", [Str(s)]) => Input::Paste(s.clone()),
+        ("GoTo", []) => Input::GoTo,
+        ("Find", []) => Input::Find,
+        ("FindNext", []) => Input::FindNext,
+        ("FindPrevious", []) => Input::FindPrevious,
+        ("Replace", []) => Input::Replace,
+        ("AddCursorAtNextOccurrence", []) => Input::AddCursorAtNextOccurrence,
+        ("SelectAllOccurrences", []) => Input::SelectAllOccurrences,
+        ("ConvertLineEndings", []) => Input::ConvertLineEndings,
+        ("SetLineBreaks", []) => Input::SetLineBreaks,
+        _ => return Err(format!("Unknown input: {}", line.trim())),
+    })
+}
+
+fn parse_move(name: &str) -> Result<Move, String> {
+    Ok(match name {
+        "Up" => Move::Up,
+        "Down" => Move::Down,
+        "Left" => Move::Left,
+        "Right" => Move::Right,
+        "ToLineStart" => Move::ToLineStart,
+        "ToLineEnd" => Move::ToLineEnd,
+        "ToBufferStart" => Move::ToBufferStart,
+        "ToBufferEnd" => Move::ToBufferEnd,
+        "ToPreviousWordStart" => Move::ToPreviousWordStart,
+        "ToNextWordEnd" => Move::ToNextWordEnd,
+        _ => return Err(format!("Unknown move: {}", name)),
+    })
+}
+
+fn parse_number<N: FromStr>(s: &str) -> Result<N, String> {
+    s.parse().map_err(|_| format!("Not a number: {}", s))
+}
+
+fn parse_xy(x: &str, y: &str) -> Result<ScreenSpaceXY, String> {
+    Ok(ScreenSpaceXY {
+        x: parse_number(x)?,
+        y: parse_number(y)?,
+    })
+}
+
+fn parse_args(s: &str) -> Result<Vec<Arg>, String> {
+    let mut output = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    loop {
+        skip_whitespace(&mut chars);
+
+        let arg = match chars.peek() {
+            None if output.is_empty() => break,
+            None => return Err("Missing argument after `,`".to_owned()),
+            Some('\'') => {
+                chars.next();
+                let s = parse_quoted(&mut chars, '\'')?;
+                let mut s_chars = s.chars();
+                match (s_chars.next(), s_chars.next()) {
+                    (Some(c), None) => Arg::Char(c),
+                    _ => return Err(format!("Not a single char: '{}'", s)),
+                }
+            }
+            Some('"') => {
+                chars.next();
+                Arg::Str(parse_quoted(&mut chars, '"')?)
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                Arg::Word(word.trim_end().to_owned())
+            }
+        };
+        output.push(arg);
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => break,
+            Some(',') => {}
+            Some(c) => return Err(format!("Expected `,` but found `{}`", c)),
+        }
+    }
+
+    Ok(output)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while let Some(c) = chars.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        chars.next();
+    }
+}
+
+/// Reads up to and including the closing `delimiter`, handling the same escapes Rust does.
+fn parse_quoted(chars: &mut impl Iterator<Item = char>, delimiter: char) -> Result<String, String> {
+    let mut output = String::new();
+
+    loop {
+        match chars.next() {
+            None => return Err(format!("Missing closing {}", delimiter)),
+            Some(c) if c == delimiter => return Ok(output),
+            Some('\\') => output.push(match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('u') => {
+                    let hex: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    Some(&hex)
+                        .filter(|hex| hex.starts_with('{'))
+                        .and_then(|hex| u32::from_str_radix(&hex[1..], 16).ok())
+                        .and_then(std::char::from_u32)
+                        .ok_or_else(|| format!("Invalid unicode escape: \\u{}}}", hex))?
+                }
+                Some(c @ '\\') | Some(c @ '\'') | Some(c @ '"') => c,
+                Some(c) => return Err(format!("Unknown escape: \\{}", c)),
+                None => return Err(format!("Missing closing {}", delimiter)),
+            }),
+            Some(c) => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,175 @@
+use super::*;
+use platform_types::{BufferView, Cmd};
+use std::path::PathBuf;
+
+macro_rules! assert_parses {
+    ($line:expr, $pattern:pat $(if $guard:expr)?) => {
+        match parse_input($line) {
+            Ok($pattern) $(if $guard)? => {}
+            other => panic!("{:?} parsed as {:?}", $line, other),
+        }
+    };
+}
+
+#[test]
+fn parse_input_handles_each_kind_of_argument() {
+    assert_parses!("Insert('\\n')", Input::Insert('\n'));
+    assert_parses!(
+        "Paste(\"a, \\\"b\\\"\\u{e9}\")",
+        Input::Paste(s) if s == "a, \"b\"\u{e9}"
+    );
+    assert_parses!(
+        "ExtendSelectionForAllCursors( ToBufferEnd )",
+        Input::ExtendSelectionForAllCursors(Move::ToBufferEnd)
+    );
+    assert_parses!(
+        "ReplaceCursors(1.5, -2)",
+        Input::ReplaceCursors(ScreenSpaceXY { x, y }) if x == 1.5 && y == -2.0
+    );
+    assert_parses!(
+        "Saved(3, \"a.txt\")",
+        Input::Saved(BufferId(3), path) if path == std::path::Path::new("a.txt")
+    );
+    assert_parses!(
+        "FileError(2, \"a.txt\", \"oops\")",
+        Input::FileError(Some(BufferId(2)), _, e) if e == "oops"
+    );
+    assert_parses!(
+        "FileError(\"a.txt\", \"oops\")",
+        Input::FileError(None, _, _)
+    );
+    assert_parses!("  Undo ", Input::Undo);
+}
+
+#[test]
+fn parse_input_rejects_bad_lines() {
+    assert!(parse_input("Insert('ab')").is_err());
+    assert!(parse_input("Insert").is_err());
+    assert!(parse_input("MoveAllCursors(Sideways)").is_err());
+    assert!(parse_input("Paste(\"unclosed)").is_err());
+    assert!(parse_input("Undo(").is_err());
+    assert!(parse_input("Frobnicate").is_err());
+}
+
+fn round_trip_path() -> PathBuf {
+    std::env::temp_dir().join(format!("headless_round_trip_{}.txt", std::process::id()))
+}
+
+/// Stands in for the editor. Each `View` shows the `Input` that led to it, `Insert('l')` loads
+/// the file at `round_trip_path` and `Insert('c')` copies "copied".
+fn stub_update_and_render(input: Input) -> UpdateAndRenderOutput {
+    let cmd = match input {
+        Input::Insert('l') => Cmd::LoadFile(round_trip_path()),
+        Input::Insert('c') => Cmd::SetClipboard("copied".to_owned()),
+        _ => Cmd::NoCmd,
+    };
+    let view = View {
+        buffers: vec![BufferView {
+            kind: BufferViewKind::Edit,
+            chars: format!("{:?}", input),
+            ..d!()
+        }],
+    };
+
+    (view, cmd)
+}
+
+fn run_stub(script: &str) -> String {
+    let mut output = Vec::new();
+    run(stub_update_and_render, &mut script.as_bytes(), &mut output).unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+/// The last line of the output is the text of the final `View`.
+fn final_text(output: &str) -> &str {
+    output.lines().last().unwrap()
+}
+
+/// The real editor. `run` calls this on a new thread each time, so each run gets a new `State`.
+fn editor_update_and_render(input: Input) -> UpdateAndRenderOutput {
+    thread_local! {
+        static STATE: std::cell::RefCell<editor::State> = std::cell::RefCell::new(editor::new());
+    }
+    STATE.with(|state| editor::update_and_render(&mut state.borrow_mut(), input))
+}
+
+/// Everything after the final `View`, which is written with `{:#?}`, so it ends with the first
+/// line that is just `}`.
+fn edit_text(output: &str) -> &str {
+    let end = "\n}\n";
+    &output[output.find(end).unwrap() + end.len()..]
+}
+
+#[test]
+fn run_gives_the_text_the_real_editor_ends_up_with() {
+    let script = "
+        Insert('a')
+        InsertLineBreak
+        Paste(\"bc\\r\\nd\")
+        ExtendSelectionForAllCursors(ToBufferStart)
+        Copy
+        MoveAllCursors(ToBufferEnd)
+        InsertLineBreak
+        # Pastes \"a\\nbc\\r\\nd\".
+        Paste
+        MoveAllCursors(ToBufferStart)
+        DeleteTo(Right)
+    ";
+    let mut output = Vec::new();
+    run(
+        editor_update_and_render,
+        &mut script.as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert_eq!(edit_text(&output), "\nbc\r\nd\na\nbc\r\nd\n");
+}
+
+#[test]
+fn run_waits_for_loaded_files_to_come_back_before_going_on() {
+    let path = round_trip_path();
+    std::fs::write(&path, "contents").unwrap();
+
+    let output = run_stub("Insert('l')");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        final_text(&output),
+        format!("{:?}", Input::LoadedFile(path, "contents".to_owned()))
+    );
+}
+
+#[test]
+fn run_pastes_what_was_last_copied() {
+    let output = run_stub("# Copies \"copied\".\nInsert('c')\n\nPaste\n");
+
+    assert_eq!(final_text(&output), "Paste(\"copied\")");
+}
+
+#[test]
+fn screenshots_are_saved_as_pngs_of_the_given_size() {
+    let path = std::env::temp_dir().join(format!("headless_screenshot_{}.png", std::process::id()));
+    let output = run_stub(&format!("Screenshot({:?}, 64, 48)\nUndo", path));
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    // The width and height are the first things in the header chunk, as big-endian `u32`s.
+    assert_eq!(&bytes[16..24], &[0, 0, 0, 64, 0, 0, 0, 48]);
+    // The script goes on afterwards.
+    assert_eq!(final_text(&output), "Undo");
+}
+
+#[test]
+fn parse_screenshot_needs_a_path_and_a_size() {
+    assert_eq!(
+        parse_screenshot("Screenshot(\"a.png\", 3, 4)"),
+        Ok((PathBuf::from("a.png"), 3, 4))
+    );
+    assert!(parse_screenshot("Screenshot(\"a.png\")").is_err());
+    assert!(parse_screenshot("Screenshot(\"a.png\", -3, 4)").is_err());
+    assert!(parse_screenshot("Screenshots(\"a.png\", 3, 4)").is_err());
+}
@@ -2,13 +2,25 @@
 // the code is licensed under the Apache 2.0 license, as described in the license file in the
 // opengl folder, to the extent that the code remains as it was
 // (at commit 90e7c7c331e9f991e11de6404b2ca073c0a09e61)
+use clipboard::{ClipboardContext, ClipboardProvider};
 use gl_layer::RenderExtras;
 use glutin::dpi::LogicalPosition;
 use glutin::{Api, GlProfile, GlRequest};
 use glyph_brush::{rusttype::Error as FontError, rusttype::Font, rusttype::Scale, *};
 use macros::d;
 
-use platform_types::{BufferView, CharDim, Input, ScreenSpaceXY, Sizes, UpdateAndRender, View};
+use platform_types::{
+    highlight_spans, BufferView, CharDim, HighlightSpan, Input, Position, ScreenSpaceXY, Sizes,
+    UpdateAndRender, View,
+};
+use std::time::{Duration, Instant};
+
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// How far, in pixels, the mouse can move between the clicks of a double or triple click.
+const MULTI_CLICK_DISTANCE: f32 = 4.0;
+/// What fraction of the distance the mouse is into the edge margin, or past the window's edge,
+/// gets scrolled each frame while dragging a selection.
+const AUTO_SCROLL_RATE: f32 = 0.125;
 
 pub struct FontInfo<'a> {
     font: Font<'a>,
@@ -112,6 +124,8 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
 
     let mut glyph_brush = get_glyph_brush(&font_info);
 
+    let mut clipboard: ClipboardContext = ClipboardProvider::new()?;
+
     let mut gl_state = gl_layer::init(&glyph_brush, |symbol| {
         glutin_context.get_proc_address(symbol) as _
     })?;
@@ -123,7 +137,7 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
         .ok_or("get_inner_size = None")?
         .to_physical(window.get_hidpi_factor());
 
-    let (mut view, mut _cmd) = update_and_render(Input::SetSizes(Sizes! {
+    let (mut view, _) = update_and_render(Input::SetSizes(Sizes! {
         screen_w: dimensions.width as f32,
         screen_h: dimensions.height as f32,
         text_char_dim: font_info.text_char_dim,
@@ -131,26 +145,14 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
     }));
 
     let (mut mouse_x, mut mouse_y) = (0.0, 0.0);
+    let mut ctrl_held = false;
+    // Whether the left mouse button is down after a single click, so moving the mouse drags out
+    // a selection.
+    let mut dragging = false;
+    // How many clicks in a row there have been, along with when and where the latest one was.
+    let mut clicks: (u8, Option<(Instant, (f32, f32))>) = (0, None);
 
-    use std::sync::mpsc::channel;
-
-    // into the editor thread
-    let (in_tx, in_rx) = channel();
-    // out of the editor thread
-    let (out_tx, out_rx) = channel();
-
-    let join_handle = std::thread::Builder::new()
-        .name("editor".to_string())
-        .spawn(move || {
-            while let Ok(input) = in_rx.recv() {
-                let pair = update_and_render(input);
-                let _hope_it_gets_there = out_tx.send(pair);
-                if let Input::Quit = input {
-                    return;
-                }
-            }
-        })
-        .expect("Could not start editor thread!");
+    let (in_tx, out_rx, join_handle) = editor_thread::spawn(update_and_render);
 
     while running {
         loop_helper.loop_start();
@@ -188,6 +190,14 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                     }
                 }
 
+                if let WindowEvent::KeyboardInput {
+                    input: KeyboardInput { modifiers, .. },
+                    ..
+                } = &event
+                {
+                    ctrl_held = modifiers.ctrl;
+                }
+
                 use platform_types::Move;
                 match event {
                     WindowEvent::CloseRequested => quit!(),
@@ -210,6 +220,30 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                             glyph_brush.resize_texture(t_w, t_h);
                         }
                     }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keypress),
+                                modifiers:
+                                    ModifiersState {
+                                        ctrl: true,
+                                        alt: true,
+                                        shift: false,
+                                        ..
+                                    },
+                                ..
+                            },
+                        ..
+                    } => match keypress {
+                        VirtualKeyCode::Up => {
+                            call_u_and_r!(Input::AddCursorAbove);
+                        }
+                        VirtualKeyCode::Down => {
+                            call_u_and_r!(Input::AddCursorBelow);
+                        }
+                        _ => (),
+                    },
                     WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
@@ -228,12 +262,101 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                         VirtualKeyCode::Key0 => {
                             call_u_and_r!(Input::ResetScroll);
                         }
+                        VirtualKeyCode::Q => {
+                            quit!();
+                        }
+                        VirtualKeyCode::Back => {
+                            call_u_and_r!(Input::DeleteTo(Move::ToPreviousWordStart));
+                        }
+                        VirtualKeyCode::Delete => {
+                            call_u_and_r!(Input::DeleteTo(Move::ToNextWordEnd));
+                        }
                         VirtualKeyCode::Home => {
                             call_u_and_r!(Input::MoveAllCursors(Move::ToBufferStart));
                         }
                         VirtualKeyCode::End => {
                             call_u_and_r!(Input::MoveAllCursors(Move::ToBufferEnd));
                         }
+                        VirtualKeyCode::Left => {
+                            call_u_and_r!(Input::MoveAllCursors(Move::ToPreviousWordStart));
+                        }
+                        VirtualKeyCode::Right => {
+                            call_u_and_r!(Input::MoveAllCursors(Move::ToNextWordEnd));
+                        }
+                        VirtualKeyCode::O => {
+                            call_u_and_r!(Input::Open);
+                        }
+                        VirtualKeyCode::G => {
+                            call_u_and_r!(Input::GoTo);
+                        }
+                        VirtualKeyCode::F => {
+                            call_u_and_r!(Input::Find);
+                        }
+                        VirtualKeyCode::H => {
+                            call_u_and_r!(Input::Replace);
+                        }
+                        VirtualKeyCode::D => {
+                            call_u_and_r!(Input::AddCursorAtNextOccurrence);
+                        }
+                        VirtualKeyCode::S => {
+                            call_u_and_r!(Input::Save);
+                        }
+                        VirtualKeyCode::Tab => {
+                            call_u_and_r!(Input::NextBuffer);
+                        }
+                        VirtualKeyCode::N => {
+                            call_u_and_r!(Input::NewScratchBuffer);
+                        }
+                        VirtualKeyCode::W => {
+                            call_u_and_r!(Input::CloseBuffer);
+                        }
+                        VirtualKeyCode::Z => {
+                            call_u_and_r!(Input::Undo);
+                        }
+                        VirtualKeyCode::Y => {
+                            call_u_and_r!(Input::Redo);
+                        }
+                        VirtualKeyCode::X => {
+                            call_u_and_r!(Input::Cut);
+                        }
+                        VirtualKeyCode::C => {
+                            call_u_and_r!(Input::Copy);
+                        }
+                        VirtualKeyCode::V => match clipboard.get_contents() {
+                            Ok(s) => {
+                                call_u_and_r!(Input::Paste(s));
+                            }
+                            Err(e) => {
+                                println!("Could not get clipboard contents:\n{}", e);
+                            }
+                        },
+                        VirtualKeyCode::Key1 => {
+                            call_u_and_r!(Input::SelectBuffer(0));
+                        }
+                        VirtualKeyCode::Key2 => {
+                            call_u_and_r!(Input::SelectBuffer(1));
+                        }
+                        VirtualKeyCode::Key3 => {
+                            call_u_and_r!(Input::SelectBuffer(2));
+                        }
+                        VirtualKeyCode::Key4 => {
+                            call_u_and_r!(Input::SelectBuffer(3));
+                        }
+                        VirtualKeyCode::Key5 => {
+                            call_u_and_r!(Input::SelectBuffer(4));
+                        }
+                        VirtualKeyCode::Key6 => {
+                            call_u_and_r!(Input::SelectBuffer(5));
+                        }
+                        VirtualKeyCode::Key7 => {
+                            call_u_and_r!(Input::SelectBuffer(6));
+                        }
+                        VirtualKeyCode::Key8 => {
+                            call_u_and_r!(Input::SelectBuffer(7));
+                        }
+                        VirtualKeyCode::Key9 => {
+                            call_u_and_r!(Input::SelectBuffer(8));
+                        }
                         _ => (),
                     },
                     WindowEvent::KeyboardInput {
@@ -257,6 +380,38 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                         VirtualKeyCode::End => {
                             call_u_and_r!(Input::ExtendSelectionForAllCursors(Move::ToBufferEnd));
                         }
+                        VirtualKeyCode::Left => {
+                            call_u_and_r!(Input::ExtendSelectionForAllCursors(
+                                Move::ToPreviousWordStart
+                            ));
+                        }
+                        VirtualKeyCode::Right => {
+                            call_u_and_r!(Input::ExtendSelectionForAllCursors(Move::ToNextWordEnd));
+                        }
+                        VirtualKeyCode::S => {
+                            call_u_and_r!(Input::SaveAs);
+                        }
+                        VirtualKeyCode::Tab => {
+                            call_u_and_r!(Input::PreviousBuffer);
+                        }
+                        VirtualKeyCode::Z => {
+                            call_u_and_r!(Input::Redo);
+                        }
+                        VirtualKeyCode::L => {
+                            call_u_and_r!(Input::SelectAllOccurrences);
+                        }
+                        VirtualKeyCode::E => {
+                            call_u_and_r!(Input::ConvertLineEndings);
+                        }
+                        VirtualKeyCode::B => {
+                            call_u_and_r!(Input::SetLineBreaks);
+                        }
+                        VirtualKeyCode::Back => {
+                            call_u_and_r!(Input::DeleteTo(Move::ToLineStart));
+                        }
+                        VirtualKeyCode::Delete => {
+                            call_u_and_r!(Input::DeleteTo(Move::ToLineEnd));
+                        }
                         _ => (),
                     },
                     WindowEvent::KeyboardInput {
@@ -275,11 +430,17 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                         ..
                     } => match keypress {
                         VirtualKeyCode::Escape => {
-                            quit!();
+                            call_u_and_r!(Input::CollapseCursors);
                         }
                         VirtualKeyCode::Back => {
                             call_u_and_r!(Input::Delete);
                         }
+                        VirtualKeyCode::Delete => {
+                            call_u_and_r!(Input::DeleteTo(Move::Right));
+                        }
+                        VirtualKeyCode::F3 => {
+                            call_u_and_r!(Input::FindNext);
+                        }
                         VirtualKeyCode::Up => {
                             call_u_and_r!(Input::MoveAllCursors(Move::Up));
                         }
@@ -333,13 +494,21 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                         VirtualKeyCode::End => {
                             call_u_and_r!(Input::ExtendSelectionForAllCursors(Move::ToLineEnd));
                         }
+                        VirtualKeyCode::F3 => {
+                            call_u_and_r!(Input::FindPrevious);
+                        }
                         _ => (),
                     },
-                    WindowEvent::ReceivedCharacter(mut c) => {
-                        if c != '\u{7f}' && c != '\u{8}' {
-                            if c == '\r' {
-                                c = '\n';
+                    WindowEvent::ReceivedCharacter(c) => {
+                        // Keys like backspace, and shortcuts like Ctrl-O or Ctrl-Tab, arrive here
+                        // as control characters too.
+                        if c == '\r' || c == '\n' {
+                            // Enter gives a CR on some platforms and an LF on others, but either
+                            // way the buffer decides which kind of line break to insert.
+                            if !ctrl_held {
+                                call_u_and_r!(Input::InsertLineBreak);
                             }
+                        } else if !c.is_control() || (!ctrl_held && c == '\t') {
                             call_u_and_r!(Input::Insert(c));
                         }
                     }
@@ -363,29 +532,109 @@ fn run_inner(update_and_render: UpdateAndRender) -> gl_layer::Res<()> {
                     } => {
                         mouse_x = x as f32;
                         mouse_y = y as f32;
+                        if dragging {
+                            call_u_and_r!(Input::DragCursors(ScreenSpaceXY {
+                                x: mouse_x,
+                                y: mouse_y
+                            }));
+                        }
                     }
                     WindowEvent::MouseInput {
                         button: MouseButton::Left,
+                        state: ElementState::Pressed,
+                        modifiers: ModifiersState { ctrl: true, .. },
                         ..
                     } => {
-                        call_u_and_r!(Input::ReplaceCursors(ScreenSpaceXY {
+                        call_u_and_r!(Input::AddCursor(ScreenSpaceXY {
                             x: mouse_x,
                             y: mouse_y
                         }));
                     }
+                    WindowEvent::MouseInput {
+                        button: MouseButton::Left,
+                        state: ElementState::Pressed,
+                        modifiers: ModifiersState { ctrl: false, .. },
+                        ..
+                    } => {
+                        let now = Instant::now();
+                        let count = match clicks {
+                            (count, Some((at, (x, y))))
+                                if now.duration_since(at) <= MULTI_CLICK_INTERVAL
+                                    && (mouse_x - x).abs() <= MULTI_CLICK_DISTANCE
+                                    && (mouse_y - y).abs() <= MULTI_CLICK_DISTANCE =>
+                            {
+                                count % 3 + 1
+                            }
+                            _ => 1,
+                        };
+                        clicks = (count, Some((now, (mouse_x, mouse_y))));
+
+                        let xy = ScreenSpaceXY {
+                            x: mouse_x,
+                            y: mouse_y,
+                        };
+                        match count {
+                            1 => {
+                                dragging = true;
+                                call_u_and_r!(Input::ReplaceCursors(xy));
+                            }
+                            2 => {
+                                call_u_and_r!(Input::SelectWord(xy));
+                            }
+                            _ => {
+                                call_u_and_r!(Input::SelectLine(xy));
+                            }
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        button: MouseButton::Left,
+                        state: ElementState::Released,
+                        ..
+                    } => {
+                        dragging = false;
+                    }
                     _ => {}
                 }
             }
         });
 
-        if running {
-            match out_rx.try_recv() {
-                Ok((v, c)) => {
-                    view = v;
-                    _cmd = c;
+        if running && dragging {
+            // Keep scrolling while a selection is dragged near, or past, the edge of the window,
+            // even if the mouse is held still.
+            let CharDim { w, h } = font_info.text_char_dim;
+            let overshoot = |coord: f32, margin: f32, length: f32| {
+                if coord < margin {
+                    coord - margin
+                } else if coord > length - margin {
+                    coord - (length - margin)
+                } else {
+                    0.0
                 }
-                _ => {}
             };
+            let dx = overshoot(mouse_x, w, dimensions.width as f32);
+            let dy = overshoot(mouse_y, h, dimensions.height as f32);
+
+            if dx != 0.0 || dy != 0.0 {
+                let _hope_it_gets_there =
+                    in_tx.send(Input::ScrollHorizontally(-dx * AUTO_SCROLL_RATE));
+                let _hope_it_gets_there =
+                    in_tx.send(Input::ScrollVertically(dy * AUTO_SCROLL_RATE));
+                let _hope_it_gets_there = in_tx.send(Input::DragCursors(ScreenSpaceXY {
+                    x: mouse_x,
+                    y: mouse_y,
+                }));
+            }
+        }
+
+        if running {
+            while let Ok((v, c)) = out_rx.try_recv() {
+                view = v;
+                editor_thread::handle_cmd(c, &in_tx, |s| {
+                    if let Err(e) = clipboard.set_contents(s) {
+                        println!("Could not set clipboard contents:\n{}", e);
+                    }
+                });
+            }
         }
 
         let width = dimensions.width as u32;
@@ -439,16 +688,17 @@ pub fn render_buffer_view<A: Clone>(
     let mut status_line_position = None;
     let mut highlight_ranges = Vec::new();
     perf_viz::start_record!("for &BufferView");
-    for &BufferView {
-        kind,
-        bounds,
-        color,
-        ref chars,
-        screen_position,
-        ref highlights,
-    } in view.buffers.iter()
-    {
+    for buffer in view.buffers.iter() {
         use platform_types::BufferViewKind;
+        let &BufferView {
+            kind,
+            bounds,
+            color,
+            chars_start,
+            screen_position,
+            ref highlights,
+            ..
+        } = buffer;
 
         // Without a background the edit buffer(s) show through the status line(s)
         if let BufferViewKind::StatusLine = kind {
@@ -456,60 +706,69 @@ pub fn render_buffer_view<A: Clone>(
         }
 
         perf_viz::record_guard!("glyph_brush.queue");
-        let text = {
-            perf_viz::record_guard!("map unprinatbles to symbols for themselves");
-            let s = chars
-                .chars()
-                .map(|c| {
-                    // map unprinatbles to symbols for themselves
-                    if c < 0x20 as char {
-                        std::char::from_u32(c as u32 | 0x2400u32).unwrap_or(c)
-                    } else {
-                        c
-                    }
-                })
-                .collect::<String>();
-            s
-        };
-        glyph_brush.queue(Section {
-            text: &text,
-            scale: if let BufferViewKind::StatusLine = kind {
-                *status_scale
-            } else {
-                *text_scale
-            },
-            screen_position,
-            bounds,
-            color,
-            layout: Layout::default_single_line(),
-            z: match kind {
-                BufferViewKind::Edit => gl_layer::EDIT_Z,
-                BufferViewKind::Cursor => gl_layer::CURSOR_Z,
-                BufferViewKind::StatusLine => gl_layer::STATUS_Z,
-            },
-            ..Section::default()
-        });
+        let lines: Vec<&str> = buffer.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            let text = {
+                perf_viz::record_guard!("map unprinatbles to symbols for themselves");
+                let s = line
+                    .chars()
+                    .map(|c| {
+                        // map unprinatbles to symbols for themselves
+                        if c < 0x20 as char {
+                            std::char::from_u32(c as u32 | 0x2400u32).unwrap_or(c)
+                        } else {
+                            c
+                        }
+                    })
+                    .collect::<String>();
+                s
+            };
+            glyph_brush.queue(Section {
+                text: &text,
+                scale: if let BufferViewKind::StatusLine = kind {
+                    *status_scale
+                } else {
+                    *text_scale
+                },
+                screen_position: (
+                    screen_position.0 + chars_start.offset.0 as f32 * text_char_dim.w,
+                    screen_position.1 + (chars_start.line + i) as f32 * text_char_dim.h,
+                ),
+                bounds,
+                color,
+                layout: Layout::default_single_line(),
+                z: match kind {
+                    BufferViewKind::Edit => gl_layer::EDIT_Z,
+                    BufferViewKind::Cursor => gl_layer::CURSOR_Z,
+                    BufferViewKind::StatusLine => gl_layer::STATUS_Z,
+                },
+                ..Section::default()
+            });
+        }
 
         let mut rect_bounds: Bounds = d!();
         rect_bounds.max = bounds.into();
 
         perf_viz::start_record!("highlight_ranges.extend");
-        highlight_ranges.extend(highlights.iter().map(|h| {
-            let (min, max) = h.get();
-
-            let mut pixel_coords: PixelCoords = d!();
-            pixel_coords.min.x = (min.offset.0 as f32 * text_char_dim.w + screen_position.0) as i32;
-
-            pixel_coords.max.x = (max.offset.0 as f32 * text_char_dim.w + screen_position.0) as i32;
-            pixel_coords.max.y = (text_char_dim.h + screen_position.1) as i32;
-
-            HighlightRange {
-                pixel_coords,
-                bounds: rect_bounds,
-                color: [0.0, 0.0, 0.0, 0.6],
-                z: gl_layer::HIGHLIGHT_Z,
-            }
-        }));
+        highlight_ranges.extend(
+            highlights
+                .iter()
+                .flat_map(|h| {
+                    highlight_pixel_coords(
+                        h.get(),
+                        &lines,
+                        chars_start,
+                        screen_position,
+                        *text_char_dim,
+                    )
+                })
+                .map(|pixel_coords| HighlightRange {
+                    pixel_coords,
+                    bounds: rect_bounds,
+                    color: [0.0, 0.0, 0.0, 0.6],
+                    z: gl_layer::HIGHLIGHT_Z,
+                }),
+        );
         perf_viz::end_record!("highlight_ranges.extend");
     }
     perf_viz::end_record!("for &BufferView");
@@ -521,3 +780,27 @@ pub fn render_buffer_view<A: Clone>(
         highlight_ranges,
     }
 }
+
+/// One rectangle for each of the `highlight_spans`.
+fn highlight_pixel_coords(
+    highlight: (Position, Position),
+    lines: &[&str],
+    chars_start: Position,
+    (x, y): (f32, f32),
+    CharDim { w, h }: CharDim,
+) -> Vec<PixelCoords> {
+    highlight_spans(highlight, lines, chars_start)
+        .into_iter()
+        .map(|HighlightSpan { line, start, end }| {
+            let mut pixel_coords: PixelCoords = d!();
+            pixel_coords.min.x = (start as f32 * w + x) as i32;
+            pixel_coords.min.y = (line as f32 * h + y) as i32;
+            pixel_coords.max.x = (end as f32 * w + x) as i32;
+            pixel_coords.max.y = ((line + 1) as f32 * h + y) as i32;
+            pixel_coords
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
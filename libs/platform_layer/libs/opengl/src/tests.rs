@@ -0,0 +1,65 @@
+use super::*;
+use platform_types::{pos, CharOffset};
+
+const CHAR_DIM: CharDim = CharDim { w: 4.0, h: 10.0 };
+
+fn coords((min_x, min_y): (i32, i32), (max_x, max_y): (i32, i32)) -> PixelCoords {
+    let mut pixel_coords: PixelCoords = d!();
+    pixel_coords.min.x = min_x;
+    pixel_coords.min.y = min_y;
+    pixel_coords.max.x = max_x;
+    pixel_coords.max.y = max_y;
+    pixel_coords
+}
+
+#[test]
+fn a_highlight_within_one_line_is_one_rectangle() {
+    assert_eq!(
+        highlight_pixel_coords(
+            (pos! {l 1 o 1}, pos! {l 1 o 3}),
+            &["abc\n", "defg"],
+            pos! {},
+            (0.0, 0.0),
+            CHAR_DIM
+        ),
+        vec![coords((4, 10), (12, 20))]
+    );
+}
+
+#[test]
+fn a_highlight_over_several_lines_covers_each_line_and_its_line_break() {
+    assert_eq!(
+        highlight_pixel_coords(
+            (pos! {l 0 o 2}, pos! {l 3 o 1}),
+            &["abc\r\n", "\n", "defg\n", "hi"],
+            pos! {},
+            (100.0, 50.0),
+            CHAR_DIM
+        ),
+        vec![
+            coords((108, 50), (120, 60)),
+            coords((100, 60), (104, 70)),
+            coords((100, 70), (120, 80)),
+            coords((100, 80), (104, 90)),
+        ]
+    );
+}
+
+#[test]
+fn only_the_visible_lines_of_a_highlight_get_rectangles() {
+    // The lines and columns before `chars_start` are scrolled off the top and left.
+    assert_eq!(
+        highlight_pixel_coords(
+            (pos! {l 0 o 0}, pos! {l 6 o 3}),
+            &["cdef\n", "", "ghij\n"],
+            pos! {l 2 o 2},
+            (-8.0, -20.0),
+            CHAR_DIM
+        ),
+        vec![
+            coords((-8, 0), (20, 10)),
+            coords((-8, 10), (0, 20)),
+            coords((-8, 20), (20, 30)),
+        ]
+    );
+}
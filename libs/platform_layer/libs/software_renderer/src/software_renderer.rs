@@ -0,0 +1,317 @@
+//! Draws a `View` into an RGBA image on the CPU, with the same font, and laid out the same way,
+//! as the OpenGL layer, so views can be looked at on machines without a GPU. The images can be
+//! saved as PNGs, for screenshots in bug reports, or compared with expected images in tests.
+//!
+//! The OpenGL layer blends in sRGB, and this does not, so the colours will not match it exactly.
+use platform_types::{
+    highlight_spans, BufferView, BufferViewKind, CharDim, HighlightSpan, Position, Sizes, View,
+};
+use rusttype::{point, Error as FontError, Font, Scale};
+use std::io::Write;
+use std::path::Path;
+
+pub type Res<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// The sizes the OpenGL layer uses, on a screen with a hidpi factor of 1.
+pub const TEXT_SIZE: f32 = 600.0;
+pub const STATUS_SIZE: f32 = 22.0;
+
+const CLEAR_COLOR: [f32; 4] = [0.02, 0.02, 0.02, 1.0];
+const HIGHLIGHT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const STATUS_BACKGROUND_COLOR: [f32; 4] = [7.0 / 256.0, 7.0 / 256.0, 7.0 / 256.0, 1.0];
+
+pub struct FontInfo {
+    font: Font<'static>,
+    text_scale: Scale,
+    text_char_dim: CharDim,
+    status_scale: Scale,
+    status_char_dim: CharDim,
+}
+
+impl FontInfo {
+    pub fn new(text_size: f32, status_size: f32) -> Result<Self, FontError> {
+        const FONT_BYTES: &[u8] =
+            include_bytes!("../../opengl/src/fonts/FiraCode-Retina-plus-CR-and-LF.ttf");
+        let font: Font<'static> = Font::from_bytes(FONT_BYTES)?;
+
+        let text_scale = Scale::uniform(text_size.round());
+        let status_scale = Scale::uniform(status_size.round());
+
+        let text_char_dim = char_dim(&font, text_scale);
+        let status_char_dim = char_dim(&font, status_scale);
+
+        Ok(Self {
+            font,
+            text_scale,
+            text_char_dim,
+            status_scale,
+            status_char_dim,
+        })
+    }
+
+    /// The sizes to tell the editor about, so that the `View`s it makes fit an image of the
+    /// given size.
+    pub fn sizes(&self, width: u32, height: u32) -> Sizes {
+        Sizes! {
+            screen_w: width as f32,
+            screen_h: height as f32,
+            text_char_dim: self.text_char_dim,
+            status_char_dim: self.status_char_dim,
+        }
+    }
+}
+
+fn char_dim(font: &Font, scale: Scale) -> CharDim {
+    CharDim {
+        w: {
+            // We currently assume the font is monospaced.
+            let em_space_char = '\u{2003}';
+            font.glyph(em_space_char)
+                .scaled(scale)
+                .h_metrics()
+                .advance_width
+        },
+        h: {
+            let v_metrics = font.v_metrics(scale);
+
+            v_metrics.ascent + -v_metrics.descent + v_metrics.line_gap
+        },
+    }
+}
+
+/// An RGBA image, with 8 bits per channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Four bytes for each pixel, row by row, starting at the top left.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, [r, g, b, a]: [f32; 4]) -> Self {
+        let pixel = [to_byte(r), to_byte(g), to_byte(b), to_byte(a)];
+        Image {
+            width,
+            height,
+            pixels: pixel
+                .iter()
+                .cloned()
+                .cycle()
+                .take(width as usize * height as usize * 4)
+                .collect(),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Blends `color` over the pixel at `(x, y)`, with `coverage` being how much of the pixel
+    /// the color covers. Pixels outside of `clip` or the image are left alone.
+    fn blend(&mut self, (x, y): (i64, i64), color: [f32; 4], coverage: f32, clip: PixelRect) {
+        if !clip.within(self.width, self.height).contains(x, y) {
+            return;
+        }
+
+        let alpha = color[3] * coverage;
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        for (byte, channel) in self.pixels[i..i + 3].iter_mut().zip(color.iter()) {
+            let old = *byte as f32 / 255.0;
+            *byte = to_byte(channel * alpha + old * (1.0 - alpha));
+        }
+        let old_alpha = self.pixels[i + 3] as f32 / 255.0;
+        self.pixels[i + 3] = to_byte(alpha + old_alpha * (1.0 - alpha));
+    }
+
+    fn fill(&mut self, rect: PixelRect, color: [f32; 4]) {
+        let rect = rect.within(self.width, self.height);
+        for y in rect.min.1..rect.max.1 {
+            for x in rect.min.0..rect.max.0 {
+                self.blend((x, y), color, 1.0, rect);
+            }
+        }
+    }
+
+    pub fn write_png(&self, output: &mut dyn Write) -> Res<()> {
+        let mut encoder = png::Encoder::new(output, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Res<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_png(&mut file)?;
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+// `f32::clamp` needs a newer Rust than the rest of the workspace does.
+#[allow(clippy::manual_clamp)]
+fn to_byte(f: f32) -> u8 {
+    (f.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+/// A rectangle of pixels, including `min` and excluding `max`.
+#[derive(Clone, Copy, Debug)]
+struct PixelRect {
+    min: (i64, i64),
+    max: (i64, i64),
+}
+
+impl PixelRect {
+    /// The pixels whose centers are inside the given rectangle, which may be infinite.
+    fn from_corners((min_x, min_y): (f32, f32), (max_x, max_y): (f32, f32)) -> Self {
+        let pixel = |f: f32| f.round().max(i64::MIN as f32).min(i64::MAX as f32) as i64;
+        PixelRect {
+            min: (pixel(min_x), pixel(min_y)),
+            max: (pixel(max_x), pixel(max_y)),
+        }
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.min.0 && x < self.max.0 && y >= self.min.1 && y < self.max.1
+    }
+
+    fn intersect(&self, other: PixelRect) -> Self {
+        PixelRect {
+            min: (self.min.0.max(other.min.0), self.min.1.max(other.min.1)),
+            max: (self.max.0.min(other.max.0), self.max.1.min(other.max.1)),
+        }
+    }
+
+    fn within(&self, width: u32, height: u32) -> Self {
+        self.intersect(PixelRect {
+            min: (0, 0),
+            max: (width as i64, height as i64),
+        })
+    }
+}
+
+/// Draws `view` the way the OpenGL layer would on a `width` by `height` window: the edit
+/// buffer, then highlights, then cursors, then the status line over a background.
+pub fn render(view: &View, font_info: &FontInfo, width: u32, height: u32) -> Image {
+    let mut image = Image::new(width, height, CLEAR_COLOR);
+
+    let in_order = |kind: &BufferViewKind| match kind {
+        BufferViewKind::Edit => 0,
+        BufferViewKind::Cursor => 1,
+        BufferViewKind::StatusLine => 2,
+    };
+    let mut buffers: Vec<&BufferView> = view.buffers.iter().collect();
+    buffers.sort_by_key(|b| in_order(&b.kind));
+
+    for buffer in buffers {
+        let (x, y) = buffer.screen_position;
+        let (scale, char_dim) = match buffer.kind {
+            BufferViewKind::StatusLine => (font_info.status_scale, font_info.status_char_dim),
+            _ => (font_info.text_scale, font_info.text_char_dim),
+        };
+        let lines: Vec<&str> = buffer.lines().collect();
+
+        if let BufferViewKind::StatusLine = buffer.kind {
+            // Without a background the edit buffer(s) show through the status line(s)
+            image.fill(
+                PixelRect::from_corners((x, y), (f32::INFINITY, y + char_dim.h)),
+                STATUS_BACKGROUND_COLOR,
+            );
+        }
+
+        let clip = PixelRect::from_corners((x, y), (x + buffer.bounds.0, y + buffer.bounds.1))
+            .within(width, height);
+        let ascent = font_info.font.v_metrics(scale).ascent;
+        for (i, line) in lines.iter().enumerate() {
+            let line_x = x + buffer.chars_start.offset.0 as f32 * char_dim.w;
+            let baseline = y + (buffer.chars_start.line + i) as f32 * char_dim.h + ascent;
+
+            for (j, c) in line.chars().enumerate() {
+                let glyph = font_info
+                    .font
+                    .glyph(printable(c))
+                    .scaled(scale)
+                    .positioned(point(line_x + j as f32 * char_dim.w, baseline));
+                let bb = match glyph.pixel_bounding_box() {
+                    Some(bb) => bb,
+                    None => continue,
+                };
+                if bb.max.x < 0
+                    || bb.min.x >= width as i32
+                    || bb.max.y < 0
+                    || bb.min.y >= height as i32
+                {
+                    continue;
+                }
+
+                glyph.draw(|gx, gy, coverage| {
+                    image.blend(
+                        (bb.min.x as i64 + gx as i64, bb.min.y as i64 + gy as i64),
+                        buffer.color,
+                        coverage,
+                        clip,
+                    )
+                });
+            }
+        }
+
+        let highlight_clip =
+            PixelRect::from_corners((0.0, 0.0), buffer.bounds).within(width, height);
+        for highlight in buffer.highlights.iter() {
+            for rect in highlight_rects(
+                highlight.get(),
+                &lines,
+                buffer.chars_start,
+                buffer.screen_position,
+                char_dim,
+            ) {
+                image.fill(rect.intersect(highlight_clip), HIGHLIGHT_COLOR);
+            }
+        }
+    }
+
+    image
+}
+
+/// Like the OpenGL layer, we show the symbols for control characters, instead of nothing.
+fn printable(c: char) -> char {
+    if c < 0x20 as char {
+        std::char::from_u32(c as u32 | 0x2400u32).unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// One rectangle for each of the `highlight_spans`, the same ones the OpenGL layer draws.
+fn highlight_rects(
+    highlight: (Position, Position),
+    lines: &[&str],
+    chars_start: Position,
+    (x, y): (f32, f32),
+    CharDim { w, h }: CharDim,
+) -> Vec<PixelRect> {
+    highlight_spans(highlight, lines, chars_start)
+        .into_iter()
+        .map(|HighlightSpan { line, start, end }| PixelRect {
+            min: ((start as f32 * w + x) as i64, (line as f32 * h + y) as i64),
+            max: (
+                (end as f32 * w + x) as i64,
+                ((line + 1) as f32 * h + y) as i64,
+            ),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
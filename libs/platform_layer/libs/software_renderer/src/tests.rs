@@ -0,0 +1,276 @@
+use super::*;
+use macros::d;
+use platform_types::{pos, CharOffset, Highlight, Input, Move};
+use std::path::PathBuf;
+
+// Small sizes keep the tests quick, and still have more than one pixel per char.
+const TEST_TEXT_SIZE: f32 = 32.0;
+const TEST_STATUS_SIZE: f32 = 16.0;
+
+fn font_info() -> FontInfo {
+    FontInfo::new(TEST_TEXT_SIZE, TEST_STATUS_SIZE).unwrap()
+}
+
+fn clear_pixel() -> [u8; 4] {
+    Image::new(1, 1, CLEAR_COLOR).pixel(0, 0)
+}
+
+fn pixels_in(image: &Image, rect: PixelRect) -> Vec<[u8; 4]> {
+    let mut output = Vec::new();
+    for y in rect.min.1..rect.max.1 {
+        for x in rect.min.0..rect.max.0 {
+            output.push(image.pixel(x as u32, y as u32));
+        }
+    }
+    output
+}
+
+#[test]
+fn an_empty_view_renders_as_the_clear_color() {
+    let image = render(&d!(), &font_info(), 7, 5);
+
+    assert_eq!((image.width, image.height), (7, 5));
+    assert!(image.pixels.chunks(4).all(|p| p == clear_pixel()));
+}
+
+#[test]
+fn highlights_cover_each_of_their_lines_and_the_line_breaks_between() {
+    let font_info = font_info();
+    let CharDim { w, h } = font_info.text_char_dim;
+    // Spaces draw nothing, and the line break is drawn inside the highlight, so only the
+    // highlights change any pixels.
+    let view = View {
+        buffers: vec![BufferView {
+            kind: BufferViewKind::Edit,
+            screen_position: (0.0, 0.0),
+            bounds: (f32::INFINITY, f32::INFINITY),
+            color: [1.0, 1.0, 1.0, 1.0],
+            chars: "   \n   ".to_owned(),
+            chars_start: pos! {},
+            line_ends: vec![4],
+            highlights: vec![Highlight::new((pos! {l 0 o 1}, pos! {l 1 o 2}))],
+        }],
+    };
+    let width = (w * 6.0) as u32;
+    let height = (h * 2.0) as u32;
+
+    let image = render(&view, &font_info, width, height);
+
+    let expected_rects = highlight_rects(
+        view.buffers[0].highlights[0].get(),
+        &["   \n", "   "],
+        pos! {},
+        (0.0, 0.0),
+        font_info.text_char_dim,
+    );
+    assert_eq!(expected_rects.len(), 2);
+    // The first line's rect includes the line break.
+    assert_eq!(expected_rects[0].max.0, (4.0 * w) as i64);
+
+    let clear = clear_pixel();
+    for y in 0..height {
+        for x in 0..width {
+            let highlighted = expected_rects
+                .iter()
+                .any(|r| r.contains(x as i64, y as i64));
+            assert_eq!(image.pixel(x, y) != clear, highlighted, "at ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn the_status_line_is_drawn_over_the_edit_buffer_with_a_background() {
+    let font_info = font_info();
+    let status_h = font_info.status_char_dim.h;
+    let width = 64;
+    let height = 64;
+    let status_y = height as f32 - status_h;
+    let view = View {
+        buffers: vec![
+            BufferView {
+                kind: BufferViewKind::StatusLine,
+                screen_position: (0.0, status_y),
+                bounds: (width as f32, status_h),
+                color: [0.3, 0.9, 0.3, 1.0],
+                chars: "".to_owned(),
+                ..d!()
+            },
+            // Drawn first, despite coming second, so the status line covers it.
+            BufferView {
+                kind: BufferViewKind::Edit,
+                screen_position: (0.0, status_y - 4.0),
+                bounds: (f32::INFINITY, f32::INFINITY),
+                color: [0.3, 0.3, 0.9, 1.0],
+                chars: "█".to_owned(),
+                ..d!()
+            },
+        ],
+    };
+
+    let image = render(&view, &font_info, width, height);
+
+    let background = Image::new(1, 1, STATUS_BACKGROUND_COLOR).pixel(0, 0);
+    let status_rect = PixelRect::from_corners((0.0, status_y), (width as f32, height as f32));
+    assert!(pixels_in(&image, status_rect)
+        .into_iter()
+        .all(|p| p == background));
+
+    let above_status_rect = PixelRect::from_corners((0.0, 0.0), (width as f32, status_y));
+    assert!(pixels_in(&image, above_status_rect)
+        .into_iter()
+        .any(|p| p[2] > p[0] && p[2] > p[1]));
+}
+
+#[test]
+fn cursors_are_drawn_in_their_color_at_their_position() {
+    let font_info = font_info();
+    let CharDim { w, h } = font_info.text_char_dim;
+    let view = View {
+        buffers: vec![BufferView {
+            kind: BufferViewKind::Cursor,
+            screen_position: (w * 2.0, 0.0),
+            bounds: (f32::INFINITY, h),
+            color: [0.9, 0.3, 0.3, 1.0],
+            chars: "▏".to_owned(),
+            ..d!()
+        }],
+    };
+    let width = (w * 4.0) as u32;
+    let height = h as u32;
+
+    let image = render(&view, &font_info, width, height);
+
+    let is_red = |p: [u8; 4]| p[0] > p[1] && p[0] > p[2];
+    let left = PixelRect::from_corners((0.0, 0.0), (w * 2.0, h));
+    let right = PixelRect::from_corners((w * 2.0, 0.0), (w * 4.0, h));
+    assert!(!pixels_in(&image, left).into_iter().any(is_red));
+    assert!(pixels_in(&image, right).into_iter().any(is_red));
+}
+
+#[test]
+fn line_breaks_are_drawn_as_their_control_pictures() {
+    let font_info = font_info();
+    let edit_view = |chars: &str| View {
+        buffers: vec![BufferView {
+            kind: BufferViewKind::Edit,
+            bounds: (f32::INFINITY, f32::INFINITY),
+            color: [1.0, 1.0, 1.0, 1.0],
+            chars: chars.to_owned(),
+            ..d!()
+        }],
+    };
+
+    let image = render(&edit_view("a\r\n"), &font_info, 64, 32);
+
+    assert_eq!(
+        image,
+        render(&edit_view("a\u{240D}\u{240A}"), &font_info, 64, 32)
+    );
+    assert_ne!(image, render(&edit_view("a"), &font_info, 64, 32));
+}
+
+#[test]
+fn write_png_gives_back_the_same_pixels_when_decoded() {
+    let view = View {
+        buffers: vec![BufferView {
+            kind: BufferViewKind::Edit,
+            screen_position: (0.0, 0.0),
+            bounds: (f32::INFINITY, f32::INFINITY),
+            color: [0.3, 0.3, 0.9, 1.0],
+            chars: "ab\ncd".to_owned(),
+            chars_start: pos! {},
+            line_ends: vec![3],
+            highlights: vec![Highlight::new((pos! {l 0 o 1}, pos! {l 1 o 1}))],
+        }],
+    };
+    let image = render(&view, &font_info(), 50, 80);
+
+    let mut png_bytes = Vec::new();
+    image.write_png(&mut png_bytes).unwrap();
+
+    let (info, mut reader) = png::Decoder::new(&png_bytes[..]).read_info().unwrap();
+    assert_eq!((info.width, info.height), (image.width, image.height));
+    assert_eq!(info.color_type, png::ColorType::RGBA);
+    let mut decoded = vec![0; info.buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, image.pixels);
+}
+
+/// Set this, to anything, to have the golden image tests write what they got into their expected
+/// files, instead of comparing against them.
+const REWRITE_GOLDEN_IMAGES: &str = "REWRITE_GOLDEN_IMAGES";
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn assert_golden(name: &str, image: &Image) {
+    let path = golden_path(name);
+
+    if std::env::var_os(REWRITE_GOLDEN_IMAGES).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image.save_png(&path).unwrap();
+        return;
+    }
+
+    let file = std::fs::File::open(&path).unwrap_or_else(|e| {
+        panic!(
+            "Could not open {}: {}\nRun with {} set to create it.",
+            path.display(),
+            e,
+            REWRITE_GOLDEN_IMAGES
+        )
+    });
+    let (info, mut reader) = png::Decoder::new(file).read_info().unwrap();
+    let mut expected = vec![0; info.buffer_size()];
+    reader.next_frame(&mut expected).unwrap();
+
+    assert!(
+        (info.width, info.height) == (image.width, image.height) && expected == image.pixels,
+        "{} does not match. Run with {} set to accept the new output.",
+        path.display(),
+        REWRITE_GOLDEN_IMAGES,
+    );
+}
+
+/// Feeds `text`, then `inputs`, to a new editor that thinks the screen is `width` by `height`,
+/// and draws the last `View`.
+fn render_editor(text: &str, inputs: Vec<Input>, width: u32, height: u32) -> Image {
+    let font_info = font_info();
+    let mut state = editor::new();
+    let mut view = d!();
+
+    let typed = text.chars().map(|c| {
+        if c == '\n' {
+            Input::InsertLineBreak
+        } else {
+            Input::Insert(c)
+        }
+    });
+    for input in std::iter::once(Input::SetSizes(font_info.sizes(width, height)))
+        .chain(typed)
+        .chain(inputs)
+    {
+        view = editor::update_and_render(&mut state, input).0;
+    }
+
+    render(&view, &font_info, width, height)
+}
+
+#[test]
+fn golden_editor_with_a_selection_across_lines() {
+    let image = render_editor(
+        "fn main() {\n\n    rote();\n}",
+        vec![
+            Input::MoveAllCursors(Move::Up),
+            Input::ExtendSelectionForAllCursors(Move::Up),
+            Input::ExtendSelectionForAllCursors(Move::Up),
+        ],
+        240,
+        160,
+    );
+
+    assert_golden("selection_across_lines", &image);
+}
@@ -0,0 +1,432 @@
+//! A platform layer that draws in a terminal, for when there is no OpenGL, like over SSH. The
+//! OpenGL layer shows a single huge line, and so does this one, scaled down to terminal cells:
+//! every line is as tall as the whole screen above the status line, with its text in the middle
+//! row, and each char is one cell wide.
+//!
+//! The key bindings are the same as the OpenGL layer's, except where terminals cannot tell keys
+//! apart. Ctrl+Shift+letter and Ctrl+number cannot be told apart from Ctrl+letter and number, so
+//! those use Alt instead, and Ctrl+PageDown and Ctrl+PageUp switch buffers in place of Ctrl+Tab.
+//! Alt+Backspace and Alt+D delete words, since many terminals send Ctrl+Backspace as Ctrl+H.
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use macros::d;
+use platform_types::{
+    highlight_spans, BufferViewKind, CharDim, HighlightSpan, Input, Move, ScreenSpaceXY, Sizes,
+    UpdateAndRender, View,
+};
+use std::io::{stdout, Write};
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+
+pub type Res<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// How long to wait for terminal events before checking for a new `View`.
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// How many columns a turn of the mouse wheel scrolls, while shift is held.
+const SCROLL_COLUMNS: f32 = 8.0;
+
+pub fn run(update_and_render: UpdateAndRender) -> Res<()> {
+    let mut stdout = stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        cursor::Hide
+    )?;
+
+    let result = run_inner(update_and_render, &mut stdout);
+
+    // Put the terminal back the way we found it, even if something went wrong.
+    let _ = execute!(
+        stdout,
+        cursor::Show,
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    );
+    let _ = terminal::disable_raw_mode();
+
+    result
+}
+
+fn run_inner(update_and_render: UpdateAndRender, stdout: &mut impl Write) -> Res<()> {
+    let (in_tx, out_rx, join_handle) = editor_thread::spawn(update_and_render);
+
+    let (mut columns, mut rows) = terminal::size()?;
+    let _hope_it_gets_there = in_tx.send(Input::SetSizes(sizes(columns, rows)));
+
+    let mut view: View = d!();
+    let mut clipboard = String::new();
+    let mut clicks: Clicks = d!();
+    let mut running = true;
+
+    while running {
+        let mut needs_redraw = false;
+
+        if event::poll(POLL_INTERVAL)? {
+            let input = match event::read()? {
+                Event::Key(key) => key_input(key, &clipboard),
+                Event::Mouse(mouse) => mouse_input(mouse, rows, &mut clicks),
+                Event::Resize(c, r) => {
+                    columns = c;
+                    rows = r;
+                    needs_redraw = true;
+                    Some(Input::SetSizes(sizes(columns, rows)))
+                }
+            };
+
+            if let Some(input) = input {
+                if let Input::Quit = input {
+                    running = false;
+                }
+                let _hope_it_gets_there = in_tx.send(input);
+            }
+        }
+
+        loop {
+            match out_rx.try_recv() {
+                Ok((v, c)) => {
+                    view = v;
+                    needs_redraw = true;
+                    editor_thread::handle_cmd(c, &in_tx, |s| clipboard = s);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if running {
+                        return Err("The editor thread stopped unexpectedly!".into());
+                    }
+                    break;
+                }
+            }
+        }
+
+        if running && needs_redraw {
+            draw(stdout, &cells(&view, columns, rows))?;
+        }
+    }
+
+    join_handle
+        .join()
+        .map_err(|_| "Could not join editor thread!")?;
+
+    Ok(())
+}
+
+fn text_char_dim(rows: u16) -> CharDim {
+    CharDim {
+        w: 1.0,
+        h: std::cmp::max(rows, 2) as f32 - 1.0,
+    }
+}
+
+fn sizes(columns: u16, rows: u16) -> Sizes {
+    Sizes! {
+        screen_w: columns as f32,
+        screen_h: rows as f32,
+        text_char_dim: text_char_dim(rows),
+        status_char_dim: CharDim { w: 1.0, h: 1.0 },
+    }
+}
+
+fn key_input(KeyEvent { code, modifiers }: KeyEvent, clipboard: &str) -> Option<Input> {
+    use KeyCode::*;
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    let shift = modifiers.contains(KeyModifiers::SHIFT);
+
+    Some(match (ctrl, alt, shift, code) {
+        (true, true, _, Up) => Input::AddCursorAbove,
+        (true, true, _, Down) => Input::AddCursorBelow,
+        (true, false, _, Char('q')) => Input::Quit,
+        (true, false, _, Char('o')) => Input::Open,
+        (true, false, _, Char('g')) => Input::GoTo,
+        (true, false, _, Char('f')) => Input::Find,
+        (true, false, _, Char('h')) => Input::Replace,
+        (true, false, _, Char('d')) => Input::AddCursorAtNextOccurrence,
+        (true, false, _, Char('s')) => Input::Save,
+        (true, false, _, Char('n')) => Input::NewScratchBuffer,
+        (true, false, _, Char('w')) => Input::CloseBuffer,
+        (true, false, _, Char('z')) => Input::Undo,
+        (true, false, _, Char('y')) => Input::Redo,
+        (true, false, _, Char('x')) => Input::Cut,
+        (true, false, _, Char('c')) => Input::Copy,
+        (true, false, _, Char('v')) => Input::Paste(clipboard.to_owned()),
+        (true, false, _, PageDown) => Input::NextBuffer,
+        (true, false, _, PageUp) => Input::PreviousBuffer,
+        (true, false, false, Home) => Input::MoveAllCursors(Move::ToBufferStart),
+        (true, false, false, End) => Input::MoveAllCursors(Move::ToBufferEnd),
+        (true, false, false, Left) => Input::MoveAllCursors(Move::ToPreviousWordStart),
+        (true, false, false, Right) => Input::MoveAllCursors(Move::ToNextWordEnd),
+        (true, false, false, Delete) => Input::DeleteTo(Move::ToNextWordEnd),
+        (true, false, true, Home) => Input::ExtendSelectionForAllCursors(Move::ToBufferStart),
+        (true, false, true, End) => Input::ExtendSelectionForAllCursors(Move::ToBufferEnd),
+        (true, false, true, Left) => Input::ExtendSelectionForAllCursors(Move::ToPreviousWordStart),
+        (true, false, true, Right) => Input::ExtendSelectionForAllCursors(Move::ToNextWordEnd),
+        (true, false, true, Delete) => Input::DeleteTo(Move::ToLineEnd),
+        (false, true, _, Char('s')) => Input::SaveAs,
+        (false, true, _, Char('l')) => Input::SelectAllOccurrences,
+        (false, true, _, Char('e')) => Input::ConvertLineEndings,
+        (false, true, _, Char('b')) => Input::SetLineBreaks,
+        (false, true, _, Char('d')) => Input::DeleteTo(Move::ToNextWordEnd),
+        (false, true, _, Char('0')) => Input::ResetScroll,
+        (false, true, _, Char(c @ '1'..='9')) => Input::SelectBuffer(c as usize - '1' as usize),
+        (false, true, _, Backspace) => Input::DeleteTo(Move::ToPreviousWordStart),
+        (false, false, _, Esc) => Input::CollapseCursors,
+        (false, false, _, Backspace) => Input::Delete,
+        (false, false, _, Delete) => Input::DeleteTo(Move::Right),
+        (false, false, _, Enter) => Input::InsertLineBreak,
+        (false, false, _, Tab) => Input::Insert('\t'),
+        (false, false, false, F(3)) => Input::FindNext,
+        (false, false, true, F(3)) => Input::FindPrevious,
+        (false, false, false, Up) => Input::MoveAllCursors(Move::Up),
+        (false, false, false, Down) => Input::MoveAllCursors(Move::Down),
+        (false, false, false, Left) => Input::MoveAllCursors(Move::Left),
+        (false, false, false, Right) => Input::MoveAllCursors(Move::Right),
+        (false, false, false, Home) => Input::MoveAllCursors(Move::ToLineStart),
+        (false, false, false, End) => Input::MoveAllCursors(Move::ToLineEnd),
+        (false, false, true, Up) => Input::ExtendSelectionForAllCursors(Move::Up),
+        (false, false, true, Down) => Input::ExtendSelectionForAllCursors(Move::Down),
+        (false, false, true, Left) => Input::ExtendSelectionForAllCursors(Move::Left),
+        (false, false, true, Right) => Input::ExtendSelectionForAllCursors(Move::Right),
+        (false, false, true, Home) => Input::ExtendSelectionForAllCursors(Move::ToLineStart),
+        (false, false, true, End) => Input::ExtendSelectionForAllCursors(Move::ToLineEnd),
+        (false, false, _, Char(c)) => Input::Insert(c),
+        _ => return None,
+    })
+}
+
+/// How many clicks in a row there have been, along with when and where the latest one was.
+#[derive(Default)]
+struct Clicks {
+    count: u8,
+    latest: Option<(Instant, (u16, u16))>,
+}
+
+fn mouse_input(
+    MouseEvent {
+        kind,
+        column,
+        row,
+        modifiers,
+    }: MouseEvent,
+    rows: u16,
+    clicks: &mut Clicks,
+) -> Option<Input> {
+    let xy = ScreenSpaceXY {
+        x: column as f32,
+        y: row as f32,
+    };
+    let shift = modifiers.contains(KeyModifiers::SHIFT);
+
+    Some(match kind {
+        MouseEventKind::Moved => Input::SetMousePos(xy),
+        MouseEventKind::Down(MouseButton::Left) if modifiers.contains(KeyModifiers::CONTROL) => {
+            Input::AddCursor(xy)
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            let now = Instant::now();
+            let count = match clicks.latest {
+                Some((when, cell))
+                    if now.duration_since(when) <= MULTI_CLICK_INTERVAL
+                        && cell == (column, row) =>
+                {
+                    clicks.count % 3 + 1
+                }
+                _ => 1,
+            };
+            *clicks = Clicks {
+                count,
+                latest: Some((now, (column, row))),
+            };
+
+            match count {
+                1 => Input::ReplaceCursors(xy),
+                2 => Input::SelectWord(xy),
+                _ => Input::SelectLine(xy),
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => Input::DragCursors(xy),
+        MouseEventKind::ScrollUp if shift => Input::ScrollHorizontally(SCROLL_COLUMNS),
+        MouseEventKind::ScrollDown if shift => Input::ScrollHorizontally(-SCROLL_COLUMNS),
+        // A turn of the wheel moves by a whole line, which is most of the screen.
+        MouseEventKind::ScrollUp => Input::ScrollVertically(-text_char_dim(rows).h),
+        MouseEventKind::ScrollDown => Input::ScrollVertically(text_char_dim(rows).h),
+        _ => return None,
+    })
+}
+
+/// What to draw in one terminal cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    c: char,
+    color: [f32; 4],
+    /// Drawn with the foreground and background swapped, for selections, cursors and the status
+    /// line.
+    inverted: bool,
+}
+
+d!(for Cell: Cell {
+    c: ' ',
+    color: [1.0, 1.0, 1.0, 1.0],
+    inverted: false,
+});
+
+/// Lays out the `View` on a grid of `columns` by `rows` cells, the same way the OpenGL layer
+/// lays it out in pixels, with each char being one cell wide and each line of the buffer as tall
+/// as `text_char_dim(rows)` says.
+fn cells(view: &View, columns: u16, rows: u16) -> Vec<Vec<Cell>> {
+    let (columns, rows) = (columns as usize, rows as usize);
+    let mut output = vec![vec![Cell::default(); columns]; rows];
+    let line_h = text_char_dim(rows as u16).h;
+    // The rows above the status line.
+    let edit_rows = rows.saturating_sub(1);
+
+    // The row that the text of the line starting at `y` is in, if that row is on screen.
+    let text_row = |y: f32| {
+        let row = (y + line_h / 2.0).floor();
+        if row >= 0.0 && (row as usize) < edit_rows {
+            Some(row as usize)
+        } else {
+            None
+        }
+    };
+    let column = |x: f32| {
+        let column = x.floor();
+        if column >= 0.0 && (column as usize) < columns {
+            Some(column as usize)
+        } else {
+            None
+        }
+    };
+
+    // Cursors are drawn last, so they show up on top of the text and selections.
+    let kind_order = |kind: &BufferViewKind| match kind {
+        BufferViewKind::Edit | BufferViewKind::StatusLine => 0,
+        BufferViewKind::Cursor => 1,
+    };
+    let mut buffers: Vec<_> = view.buffers.iter().collect();
+    buffers.sort_by_key(|b| kind_order(&b.kind));
+
+    for buffer in buffers {
+        let (x, y) = buffer.screen_position;
+        match buffer.kind {
+            BufferViewKind::Edit => {
+                let lines: Vec<&str> = buffer.lines().collect();
+                let chars_start = buffer.chars_start;
+
+                for (i, line) in lines.iter().enumerate() {
+                    let line_index = chars_start.line + i;
+                    let row = match text_row(y + line_index as f32 * line_h) {
+                        Some(row) => row,
+                        None => continue,
+                    };
+
+                    for (j, c) in line.chars().enumerate() {
+                        if let Some(col) = column(x + (chars_start.offset.0 + j) as f32) {
+                            output[row][col].c = printable(c);
+                            output[row][col].color = buffer.color;
+                        }
+                    }
+                }
+
+                // Only bother with the part that is on screen.
+                let first_visible = (-x).max(0.0) as usize;
+                for highlight in buffer.highlights.iter() {
+                    for HighlightSpan { line, start, end } in
+                        highlight_spans(highlight.get(), &lines, chars_start)
+                    {
+                        let row = match text_row(y + line as f32 * line_h) {
+                            Some(row) => row,
+                            None => continue,
+                        };
+                        let from = std::cmp::max(start, first_visible);
+                        let to = std::cmp::min(end, first_visible + columns);
+                        for offset in from..to {
+                            if let Some(col) = column(x + offset as f32) {
+                                output[row][col].inverted = true;
+                            }
+                        }
+                    }
+                }
+            }
+            BufferViewKind::StatusLine => {
+                let row = y.floor();
+                if row >= 0.0 && (row as usize) < rows {
+                    let row = row as usize;
+                    for cell in output[row].iter_mut() {
+                        cell.inverted = true;
+                    }
+                    for (j, c) in buffer.chars.chars().take(columns).enumerate() {
+                        output[row][j].c = printable(c);
+                        output[row][j].color = buffer.color;
+                    }
+                }
+            }
+            BufferViewKind::Cursor => {
+                if let (Some(row), Some(col)) = (text_row(y), column(x)) {
+                    let cell = &mut output[row][col];
+                    cell.color = buffer.color;
+                    cell.inverted = !cell.inverted;
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Control characters would move the terminal's cursor around, or worse, so we show the symbols
+/// for them instead.
+fn printable(c: char) -> char {
+    match c {
+        '\u{0}'..='\u{1f}' => std::char::from_u32(c as u32 | 0x2400u32).unwrap_or(c),
+        '\u{7f}' => '\u{2421}',
+        _ => c,
+    }
+}
+
+fn to_color([r, g, b, _]: [f32; 4]) -> Color {
+    let channel = |f: f32| (f * 255.0) as u8;
+    Color::Rgb {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+    }
+}
+
+fn draw(out: &mut impl Write, cells: &[Vec<Cell>]) -> Res<()> {
+    let mut current_style = None;
+
+    for (y, row) in cells.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, y as u16))?;
+        for cell in row.iter() {
+            let style = (cell.color, cell.inverted);
+            if current_style != Some(style) {
+                queue!(
+                    out,
+                    SetForegroundColor(to_color(cell.color)),
+                    SetAttribute(if cell.inverted {
+                        Attribute::Reverse
+                    } else {
+                        Attribute::NoReverse
+                    })
+                )?;
+                current_style = Some(style);
+            }
+            queue!(out, Print(cell.c))?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
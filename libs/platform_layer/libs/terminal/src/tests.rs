@@ -0,0 +1,125 @@
+use super::*;
+use platform_types::{pos, BufferView, CharOffset, Highlight, Position};
+
+/// A `BufferView` whose lines each end after a `\n` in `chars`.
+fn buffer_view(
+    kind: BufferViewKind,
+    screen_position: (f32, f32),
+    chars: &str,
+    chars_start: Position,
+    highlights: Vec<Highlight>,
+) -> BufferView {
+    BufferView {
+        kind,
+        screen_position,
+        bounds: (f32::INFINITY, f32::INFINITY),
+        color: [1.0, 1.0, 1.0, 1.0],
+        chars: chars.to_owned(),
+        chars_start,
+        line_ends: chars.match_indices('\n').map(|(i, _)| i + 1).collect(),
+        highlights,
+    }
+}
+
+fn row_chars(cells: &[Vec<Cell>], row: usize) -> String {
+    cells[row].iter().map(|cell| cell.c).collect()
+}
+
+fn inverted_columns(cells: &[Vec<Cell>], row: usize) -> Vec<usize> {
+    cells[row]
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| cell.inverted)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[test]
+fn each_line_fills_the_screen_above_the_status_line() {
+    // With 5 rows, each line is 4 rows tall, so its text goes in row 2.
+    let view = View {
+        buffers: vec![
+            buffer_view(BufferViewKind::Edit, (0.0, 0.0), "ab\ncd", pos! {}, vec![]),
+            buffer_view(
+                BufferViewKind::StatusLine,
+                (0.0, 4.0),
+                "hi",
+                pos! {},
+                vec![],
+            ),
+        ],
+    };
+
+    let cells = cells(&view, 4, 5);
+
+    assert_eq!(
+        (0..5).map(|row| row_chars(&cells, row)).collect::<Vec<_>>(),
+        vec!["    ", "    ", "ab\u{240A} ", "    ", "hi  "]
+    );
+    assert_eq!(inverted_columns(&cells, 2), Vec::<usize>::new());
+    assert_eq!(inverted_columns(&cells, 4), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn scrolling_down_a_line_shows_the_next_line_at_the_chars_start_column() {
+    let view = View {
+        buffers: vec![buffer_view(
+            BufferViewKind::Edit,
+            (-1.0, -4.0),
+            "d",
+            pos! {l 1 o 1},
+            vec![],
+        )],
+    };
+
+    let cells = cells(&view, 4, 5);
+
+    assert_eq!(row_chars(&cells, 2), "d   ");
+}
+
+#[test]
+fn highlights_include_the_line_break_on_all_but_the_last_line() {
+    let highlights = || vec![Highlight::new((pos! {l 0 o 1}, pos! {l 1 o 1}))];
+    let view = View {
+        buffers: vec![buffer_view(
+            BufferViewKind::Edit,
+            (0.0, 0.0),
+            "ab\ncd",
+            pos! {},
+            highlights(),
+        )],
+    };
+    assert_eq!(inverted_columns(&cells(&view, 4, 5), 2), vec![1, 2]);
+
+    let view = View {
+        buffers: vec![buffer_view(
+            BufferViewKind::Edit,
+            (0.0, -4.0),
+            "ab\ncd",
+            pos! {},
+            highlights(),
+        )],
+    };
+    assert_eq!(inverted_columns(&cells(&view, 4, 5), 2), vec![0]);
+}
+
+#[test]
+fn cursors_stand_out_from_highlights_and_control_chars_are_shown_as_symbols() {
+    let view = View {
+        buffers: vec![
+            buffer_view(BufferViewKind::Cursor, (1.0, 0.0), "", pos! {}, vec![]),
+            buffer_view(
+                BufferViewKind::Edit,
+                (0.0, 0.0),
+                "a\tb\u{7f}",
+                pos! {},
+                vec![Highlight::new((pos! {l 0 o 0}, pos! {l 0 o 2}))],
+            ),
+        ],
+    };
+
+    let cells = cells(&view, 4, 5);
+
+    assert_eq!(row_chars(&cells, 2), "a\u{2409}b\u{2421}");
+    assert_eq!(inverted_columns(&cells, 2), vec![0]);
+}
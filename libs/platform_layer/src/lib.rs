@@ -1,9 +1,57 @@
-// We might have different platform layer options later, so let's keep this separate.
+use macros::d;
 use platform_types::UpdateAndRender;
-pub fn run(update_and_render: UpdateAndRender) {
-    let result = opengl::run(update_and_render);
+use std::path::PathBuf;
 
-    if let Err(e) = result {
-        println!("opengl::run(update_and_render) error:\n{}", e);
+/// Which platform layer to run the editor on.
+pub enum Kind {
+    OpenGL,
+    /// Runs the script at the path, or from stdin if there is no path, instead of opening a
+    /// window. See the `headless` crate for what a script looks like.
+    Headless(Option<PathBuf>),
+    /// Draws in the terminal the editor was started from, for when there is no window to open.
+    Terminal,
+}
+
+d!(for Kind: Kind::OpenGL);
+
+pub fn run(update_and_render: UpdateAndRender, kind: Kind) {
+    match kind {
+        Kind::OpenGL => {
+            let result = opengl::run(update_and_render);
+
+            if let Err(e) = result {
+                println!("opengl::run(update_and_render) error:\n{}", e);
+            }
+        }
+        Kind::Terminal => {
+            let result = terminal::run(update_and_render);
+
+            // We print this after the terminal is restored, so it does not get drawn over.
+            if let Err(e) = result {
+                eprintln!("terminal::run(update_and_render) error:\n{}", e);
+                std::process::exit(1);
+            }
+        }
+        Kind::Headless(script_path) => {
+            use std::io::{stdin, stdout, BufReader};
+            let stdout = stdout();
+            let mut output = stdout.lock();
+
+            let result = match script_path {
+                Some(path) => std::fs::File::open(&path)
+                    .map_err(|e| format!("Could not open {}: {}", path.display(), e).into())
+                    .and_then(|file| {
+                        headless::run(update_and_render, &mut BufReader::new(file), &mut output)
+                    }),
+                None => headless::run(update_and_render, &mut stdin().lock(), &mut output),
+            };
+
+            // The output is the whole point of running headless, so if we cannot give it, we
+            // want scripts running us to notice.
+            if let Err(e) = result {
+                eprintln!("headless::run(update_and_render) error:\n{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
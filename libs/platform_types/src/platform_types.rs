@@ -1,5 +1,6 @@
 use macros::{d, fmt_debug, fmt_display, integer_newtype, usize_newtype};
 use std::ops::{Add, Sub};
+use std::path::PathBuf;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Move {
@@ -11,6 +12,8 @@ pub enum Move {
     ToLineEnd,
     ToBufferStart,
     ToBufferEnd,
+    ToPreviousWordStart,
+    ToNextWordEnd,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -25,12 +28,14 @@ impl From<ScreenSpaceXY> for (f32, f32) {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Input {
     None,
     Quit,
     Insert(char),
+    InsertLineBreak,
     Delete,
+    DeleteTo(Move),
     ResetScroll,
     ScrollVertically(f32),
     ScrollHorizontally(f32),
@@ -39,6 +44,40 @@ pub enum Input {
     MoveAllCursors(Move),
     ExtendSelectionForAllCursors(Move),
     ReplaceCursors(ScreenSpaceXY),
+    AddCursor(ScreenSpaceXY),
+    DragCursors(ScreenSpaceXY),
+    SelectWord(ScreenSpaceXY),
+    SelectLine(ScreenSpaceXY),
+    AddCursorAbove,
+    AddCursorBelow,
+    CollapseCursors,
+    Open,
+    Save,
+    SaveAs,
+    LoadedFile(PathBuf, String),
+    Saved(BufferId, PathBuf),
+    SavedAs(BufferId, PathBuf),
+    /// The `BufferId` is the buffer whose save failed, if it was a save that failed.
+    FileError(Option<BufferId>, PathBuf, String),
+    NextBuffer,
+    PreviousBuffer,
+    SelectBuffer(usize),
+    NewScratchBuffer,
+    CloseBuffer,
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste(String),
+    GoTo,
+    Find,
+    FindNext,
+    FindPrevious,
+    Replace,
+    AddCursorAtNextOccurrence,
+    SelectAllOccurrences,
+    ConvertLineEndings,
+    SetLineBreaks,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -244,14 +283,94 @@ pub struct BufferView {
     pub screen_position: (f32, f32),
     pub bounds: (f32, f32),
     pub color: [f32; 4],
-    //TODO make this a &str or a char iterator
+    /// For `Edit` views, only the part of the buffer that is on screen, line breaks included, so
+    /// they can be drawn. That part starts at `chars_start`, so the first char is drawn
+    /// `chars_start` lines and columns away from `screen_position`, which is where the start of
+    /// the buffer would be.
     pub chars: String,
+    pub chars_start: Position,
+    /// The byte index in `chars` where each line but the last ends. The line breaks in `chars`
+    /// cannot be used for this, since a line's line break might not be on screen.
+    pub line_ends: Vec<usize>,
     pub highlights: Vec<Highlight>,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl BufferView {
+    /// The lines of `chars`, split at `line_ends`.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        let starts = std::iter::once(0).chain(self.line_ends.iter().cloned());
+        let ends = self
+            .line_ends
+            .iter()
+            .cloned()
+            .chain(std::iter::once(self.chars.len()));
+        starts
+            .zip(ends)
+            .map(move |(start, end)| self.chars.get(start..end).unwrap_or_default())
+    }
+}
+
+/// The part of a line that a highlight covers, from `start` up to `end`, in chars from the start
+/// of the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One span for each line of the highlight that is among `lines`, the visible lines of a
+/// `BufferView`, which start at `chars_start`. Every line but the last also has its line break
+/// highlighted, so empty lines in a selection still show up.
+pub fn highlight_spans(
+    (min, max): (Position, Position),
+    lines: &[&str],
+    chars_start: Position,
+) -> Vec<HighlightSpan> {
+    let mut output = Vec::new();
+
+    let first_line = std::cmp::max(min.line, chars_start.line);
+    let last_line = std::cmp::min(max.line, chars_start.line + lines.len());
+    for line in first_line..=last_line {
+        let text = match lines.get(line - chars_start.line) {
+            Some(text) => text,
+            None => break,
+        };
+
+        let start = if line == min.line { min.offset.0 } else { 0 };
+        let end = if line == max.line {
+            max.offset.0
+        } else {
+            // The visible part of the line includes its line break, if that is visible.
+            chars_start.offset.0 + text.chars().count()
+        };
+
+        output.push(HighlightSpan { line, start, end });
+    }
+
+    output
+}
+
+/// Names one of the editor's buffers for as long as it is open. Unlike the buffer's index, this
+/// does not change when other buffers are closed, so it can be sent off with a `Cmd` and still
+/// mean the same buffer when the answer comes back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BufferId(pub usize);
+
+/// Things the editor wants the platform layer to do on its behalf, like file I/O. The platform
+/// layer reports back how it went by sending the corresponding `Input` variant.
+#[derive(Debug, Clone)]
 pub enum Cmd {
-    NoCmd, //The plan is to communicate things like saving to the platform layer with this
+    NoCmd,
+    /// Answered with `Input::LoadedFile` or `Input::FileError`.
+    LoadFile(PathBuf),
+    /// Write the `String` to the path. The `BufferId` is the buffer the contents came from.
+    /// Answered with `Input::Saved` or `Input::FileError`.
+    SaveFile(BufferId, PathBuf, String),
+    /// Like `SaveFile`, but answered with `Input::SavedAs` on success, since the buffer's path
+    /// should change.
+    SaveFileAs(BufferId, PathBuf, String),
+    SetClipboard(String),
 }
 
 d!(for Cmd : Cmd::NoCmd);
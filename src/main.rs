@@ -1,11 +1,12 @@
 use macros::d;
-use platform_types::{Input, UpdateAndRenderOutput};
+use platform_types::{CharOffset, Input, Position, UpdateAndRenderOutput};
+use std::path::PathBuf;
 
 fn update_and_render(input: Input) -> UpdateAndRenderOutput {
     use lazy_static::lazy_static;
     lazy_static! {
         static ref STATE_MUTEX: std::sync::Mutex<editor::State> =
-            std::sync::Mutex::new(editor::new());
+            std::sync::Mutex::new(initial_state());
     }
     match STATE_MUTEX.try_lock() {
         Ok(mut state) => editor::update_and_render(&mut state, input),
@@ -18,6 +19,149 @@ fn update_and_render(input: Input) -> UpdateAndRenderOutput {
     }
 }
 
+/// A file to open on startup, along with where to put the cursor. `line` and `column` are
+/// counted from 1, like compilers and `grep -n` do.
+#[derive(Debug, PartialEq)]
+struct FileArg {
+    path: PathBuf,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl FileArg {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line.unwrap_or(1).saturating_sub(1),
+            offset: CharOffset(self.column.unwrap_or(1).saturating_sub(1)),
+        }
+    }
+}
+
+/// Accepts paths in any of these forms: `path`, `path:line`, `path:line:col`, and `+line path`,
+/// where the `+line` applies to the path after it.
+fn parse_file_args<I: IntoIterator<Item = String>>(args: I) -> Vec<FileArg> {
+    let mut output = Vec::new();
+    let mut pending_line = None;
+
+    for arg in args {
+        if arg.len() > 1 && arg.starts_with('+') {
+            if let Ok(line) = arg[1..].parse() {
+                pending_line = Some(line);
+                continue;
+            }
+        }
+
+        let mut file_arg = parse_path_with_position(&arg);
+        if let Some(line) = pending_line.take() {
+            file_arg.line = Some(line);
+        }
+        output.push(file_arg);
+    }
+
+    output
+}
+
+fn parse_path_with_position(arg: &str) -> FileArg {
+    // We take numbers off the end so paths containing colons, like `C:\foo.rs`, still work.
+    fn split_number(s: &str) -> Option<(&str, usize)> {
+        let colon_index = s.rfind(':')?;
+        let n = s[colon_index + 1..].parse().ok()?;
+        Some((&s[..colon_index], n))
+    }
+
+    let (path, line, column) = match split_number(arg) {
+        Some((rest, last)) => match split_number(rest) {
+            Some((path, line)) => (path, Some(line), Some(last)),
+            None => (rest, Some(last), None),
+        },
+        None => (arg, None, None),
+    };
+
+    FileArg {
+        path: PathBuf::from(path),
+        line,
+        column,
+    }
+}
+
+const HEADLESS: &str = "--headless";
+const HEADLESS_SCRIPT: &str = "--headless=";
+const TERMINAL: &str = "--terminal";
+
+/// Flags are any arguments that start with `--`. Everything else is a file to open.
+fn parse_flag(scroll_settings: &mut editor::ScrollSettings, flag: &str) {
+    const SCROLL_MARGIN: &str = "--scroll-margin=";
+
+    if flag == HEADLESS || flag.starts_with(HEADLESS_SCRIPT) || flag == TERMINAL {
+        // These choose the platform layer, which happens in `platform_layer_kind`.
+    } else if flag == "--center-cursor" {
+        scroll_settings.center_horizontally = true;
+    } else if flag.starts_with(SCROLL_MARGIN) {
+        match flag[SCROLL_MARGIN.len()..].parse() {
+            Ok(margin) => scroll_settings.margin = margin,
+            Err(e) => eprintln!("Could not parse {}: {}", flag, e),
+        }
+    } else {
+        eprintln!("Unknown flag {}", flag);
+    }
+}
+
+fn initial_state() -> editor::State {
+    let mut state = editor::new();
+
+    let (flags, file_args): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+
+    let mut scroll_settings = d!();
+    for flag in flags {
+        parse_flag(&mut scroll_settings, &flag);
+    }
+    state.set_scroll_settings(scroll_settings);
+
+    for file_arg in parse_file_args(file_args) {
+        let contents = match std::fs::read_to_string(&file_arg.path) {
+            Ok(contents) => contents,
+            // Opening a file that doesn't exist yet is how you create one.
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => d!(),
+            Err(e) => {
+                eprintln!("Could not open {}: {}", file_arg.path.display(), e);
+                continue;
+            }
+        };
+
+        let position = file_arg.position();
+        state.open_at(file_arg.path, contents, position);
+    }
+
+    state
+}
+
+/// `--headless` runs a script of inputs from stdin, and `--headless=path` runs the one at `path`,
+/// instead of opening a window.
+fn platform_layer_kind<I: IntoIterator<Item = String>>(args: I) -> platform_layer::Kind {
+    let mut output = d!();
+
+    for arg in args {
+        if arg == HEADLESS {
+            output = platform_layer::Kind::Headless(None);
+        } else if arg.starts_with(HEADLESS_SCRIPT) {
+            let path = PathBuf::from(&arg[HEADLESS_SCRIPT.len()..]);
+            output = platform_layer::Kind::Headless(Some(path));
+        } else if arg == TERMINAL {
+            output = platform_layer::Kind::Terminal;
+        }
+    }
+
+    output
+}
+
 fn main() {
-    platform_layer::run(update_and_render);
+    platform_layer::run(
+        update_and_render,
+        platform_layer_kind(std::env::args().skip(1)),
+    );
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,101 @@
+use super::*;
+use platform_types::pos;
+
+fn file_arg(path: &str, line: Option<usize>, column: Option<usize>) -> FileArg {
+    FileArg {
+        path: PathBuf::from(path),
+        line,
+        column,
+    }
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn parse_path_with_position_accepts_a_plain_path() {
+    assert_eq!(
+        parse_path_with_position("src/main.rs"),
+        file_arg("src/main.rs", None, None)
+    );
+}
+
+#[test]
+fn parse_path_with_position_accepts_a_line() {
+    assert_eq!(
+        parse_path_with_position("src/main.rs:12"),
+        file_arg("src/main.rs", Some(12), None)
+    );
+}
+
+#[test]
+fn parse_path_with_position_accepts_a_line_and_column() {
+    assert_eq!(
+        parse_path_with_position("src/main.rs:12:5"),
+        file_arg("src/main.rs", Some(12), Some(5))
+    );
+}
+
+#[test]
+fn parse_path_with_position_leaves_colons_that_are_not_before_numbers_in_the_path() {
+    assert_eq!(
+        parse_path_with_position("C:\\foo.rs"),
+        file_arg("C:\\foo.rs", None, None)
+    );
+    assert_eq!(
+        parse_path_with_position("C:\\foo.rs:3:4"),
+        file_arg("C:\\foo.rs", Some(3), Some(4))
+    );
+    assert_eq!(
+        parse_path_with_position("a:b:7"),
+        file_arg("a:b", Some(7), None)
+    );
+    assert_eq!(
+        parse_path_with_position("notes:"),
+        file_arg("notes:", None, None)
+    );
+}
+
+#[test]
+fn parse_path_with_position_only_takes_two_numbers_off_the_end() {
+    assert_eq!(
+        parse_path_with_position("1:2:3"),
+        file_arg("1", Some(2), Some(3))
+    );
+}
+
+#[test]
+fn parse_file_args_applies_a_plus_line_to_the_next_path_only() {
+    assert_eq!(
+        parse_file_args(args(&["+7", "a.txt", "b.txt:2:3", "c.txt"])),
+        vec![
+            file_arg("a.txt", Some(7), None),
+            file_arg("b.txt", Some(2), Some(3)),
+            file_arg("c.txt", None, None),
+        ]
+    );
+}
+
+#[test]
+fn parse_file_args_prefers_a_plus_line_over_one_after_a_colon() {
+    assert_eq!(
+        parse_file_args(args(&["+7", "a.txt:2:3"])),
+        vec![file_arg("a.txt", Some(7), Some(3))]
+    );
+}
+
+#[test]
+fn parse_file_args_treats_a_plus_without_a_number_as_a_path() {
+    assert_eq!(
+        parse_file_args(args(&["+", "+x"])),
+        vec![file_arg("+", None, None), file_arg("+x", None, None)]
+    );
+}
+
+#[test]
+fn file_arg_position_counts_from_one() {
+    assert_eq!(file_arg("a", Some(3), Some(2)).position(), pos! {l 2 o 1});
+    assert_eq!(file_arg("a", None, None).position(), pos! {l 0 o 0});
+    assert_eq!(file_arg("a", Some(0), Some(0)).position(), pos! {l 0 o 0});
+}